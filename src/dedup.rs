@@ -0,0 +1,309 @@
+//! Removal of paths drawn more than once, and of straight segments that
+//! run over a part already drawn, so lasers and plotters don't go over
+//! the same line twice.
+
+use crate::simplify::segment_distance;
+use crate::{flatten, Pt};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use usvg::{PathData, PathSegment};
+
+/// Width of the buckets of segment directions, in radians.
+const ANGLE_STEP: f64 = 1.0 / 64.0;
+/// Sine of the largest angle between segments that run over each other,
+/// however short they are, so corners are never cut.
+const MAX_SIN: f64 = 0.25;
+/// Smallest tolerance used, so exact copies still match after rounding.
+const MIN_TOLERANCE: f64 = 1e-7;
+/// Most points added along a flattened segment when comparing paths.
+const MAX_DENSIFY: usize = 16;
+
+/// What was removed.
+#[derive(Debug, Default)]
+pub struct Removed {
+    pub paths: usize,
+    pub segments: usize,
+}
+
+/// Drops the paths of `paths` that run within `tolerance` of an earlier
+/// path all along, in either direction, then cuts the parts of straight
+/// segments that lie on an earlier straight segment. Only the paths for
+/// which `editable` is true are changed. Returns the paths left, with
+/// their index in `paths`.
+pub fn dedup(
+    paths: Vec<PathData>,
+    editable: impl Fn(usize) -> bool,
+    tolerance: f64,
+    accuracy: f64,
+) -> (Vec<PathData>, Vec<usize>, Removed) {
+    let tolerance = tolerance.max(MIN_TOLERANCE);
+    let mut removed = Removed::default();
+
+    let outlines: Vec<Option<Outline>> = paths.iter().map(|path| Outline::new(path, accuracy, tolerance)).collect();
+    let mut kept: Vec<usize> = Vec::with_capacity(paths.len());
+    for (index, outline) in outlines.iter().enumerate() {
+        let duplicate = editable(index) && outline.as_ref().is_some_and(|outline| {
+            kept.iter().filter_map(|&k| outlines[k].as_ref()).any(|other| outline.matches(other, tolerance))
+        });
+        if duplicate {
+            removed.paths += 1;
+        } else {
+            kept.push(index);
+        }
+    }
+
+    // Offset buckets as wide as a long segment turned by ANGLE_STEP moves.
+    let extent = outlines.iter().flatten().map(|outline| (outline.max - outline.min).length()).fold(0.0, f64::max);
+    let mut lines = Lines {
+        tolerance,
+        cell: tolerance.max(extent * ANGLE_STEP),
+        buckets: HashMap::new(),
+        segments: Vec::new(),
+    };
+    let mut left = Vec::with_capacity(kept.len());
+    let mut sources = Vec::with_capacity(kept.len());
+    let mut paths: Vec<Option<PathData>> = paths.into_iter().map(Some).collect();
+    for index in kept {
+        let path = paths[index].take().unwrap_or_default();
+        let path = lines.trim(&path, editable(index), &mut removed.segments);
+        if !path.is_empty() {
+            left.push(path);
+            sources.push(index);
+        }
+    }
+    (left, sources, removed)
+}
+
+/// The flattened subpaths of a path and their bounding box.
+struct Outline {
+    lines: Vec<Vec<Pt>>,
+    /// Points along the lines no further than the tolerance apart, or
+    /// as many as `MAX_DENSIFY` for every flattened segment.
+    points: Vec<Pt>,
+    min: Pt,
+    max: Pt,
+}
+
+impl Outline {
+    fn new(path: &PathData, accuracy: f64, tolerance: f64) -> Option<Outline> {
+        let lines: Vec<Vec<Pt>> = flatten(path, accuracy).into_iter().map(|(line, _)| line).collect();
+        let mut points = Vec::new();
+        for line in &lines {
+            points.extend(line.first());
+            for w in line.windows(2) {
+                let steps = ((w[1] - w[0]).length() / tolerance).ceil().clamp(1.0, MAX_DENSIFY as f64) as usize;
+                points.extend((1..=steps).map(|i| w[0].lerp(w[1], i as f64 / steps as f64)));
+            }
+        }
+        let min = points.iter().copied().reduce(Pt::min)?;
+        let max = points.iter().copied().reduce(Pt::max)?;
+        Some(Outline { lines, points, min, max })
+    }
+
+    fn distance(&self, pt: Pt) -> f64 {
+        let mut dist = f64::INFINITY;
+        for line in &self.lines {
+            if let [single] = line[..] {
+                dist = dist.min((pt - single).length());
+            }
+            for w in line.windows(2) {
+                dist = dist.min(segment_distance(pt, w[0], w[1]));
+            }
+        }
+        dist
+    }
+
+    /// Whether each outline runs within `tolerance` of the other.
+    fn matches(&self, other: &Outline, tolerance: f64) -> bool {
+        let near = |a: Pt, b: Pt| (a - b).abs().x <= tolerance && (a - b).abs().y <= tolerance;
+        near(self.min, other.min) && near(self.max, other.max)
+            && self.points.iter().all(|&pt| other.distance(pt) <= tolerance)
+            && other.points.iter().all(|&pt| self.distance(pt) <= tolerance)
+    }
+}
+
+/// The straight segments drawn so far, bucketed by their direction and
+/// by how far along the normal of that direction they are.
+struct Lines {
+    tolerance: f64,
+    cell: f64,
+    buckets: HashMap<(i64, i64), Vec<usize>>,
+    segments: Vec<(Pt, Pt)>,
+}
+
+impl Lines {
+    /// Sine of the largest angle between a segment `len` long and a line
+    /// it stays within `tolerance` of.
+    fn max_sin(&self, len: f64) -> f64 {
+        (2.0 * self.tolerance / len).min(MAX_SIN)
+    }
+
+    /// The buckets of every direction within `max_sin(len)` of the segment
+    /// from `a` to `b`, for its offsets widened by `margin`.
+    fn buckets(&self, a: Pt, b: Pt, margin: f64) -> Vec<(i64, i64)> {
+        let d = b - a;
+        let angle = d.y.atan2(d.x).rem_euclid(PI);
+        let window = self.max_sin(d.length()).asin();
+        let count = (PI / ANGLE_STEP).ceil() as i64;
+        let first = ((angle - window) / ANGLE_STEP).floor() as i64;
+        let last = ((angle + window) / ANGLE_STEP).floor() as i64;
+        let mut keys = Vec::new();
+        for k in first..=last.min(first + count - 1) {
+            // Directions wrap around, the offsets are taken along the
+            // normal of the bucket so they wrap with them.
+            let k = k.rem_euclid(count);
+            let center = (k as f64 + 0.5) * ANGLE_STEP;
+            let normal = Pt::new(-center.sin(), center.cos());
+            let (p, q) = (normal.dot(a), normal.dot(b));
+            let lo = ((p.min(q) - margin) / self.cell).floor() as i64;
+            let hi = ((p.max(q) + margin) / self.cell).floor() as i64;
+            keys.extend((lo..=hi).map(|o| (k, o)));
+        }
+        keys
+    }
+
+    fn add(&mut self, a: Pt, b: Pt) {
+        for key in self.buckets(a, b, self.tolerance) {
+            self.buckets.entry(key).or_default().push(self.segments.len());
+        }
+        self.segments.push((a, b));
+    }
+
+    /// The parts of the segment from `a` to `b` not drawn yet, as
+    /// intervals of `[0, 1]`. A part is drawn when it runs within the
+    /// tolerance of an earlier segment, and one of them is short enough
+    /// to fit along the other.
+    fn uncovered(&self, a: Pt, b: Pt, parts: &mut Vec<(f64, f64)>) {
+        parts.clear();
+        let d = b - a;
+        let len = d.length();
+        let mut covered = Vec::new();
+        let mut seen = Vec::new();
+        for key in self.buckets(a, b, 0.0) {
+            for &i in self.buckets.get(&key).into_iter().flatten() {
+                if seen.contains(&i) {
+                    continue;
+                }
+                seen.push(i);
+                let (p, q) = self.segments[i];
+                let e = q - p;
+                let e_len = e.length();
+                if d.cross(e).abs() / (len * e_len) > self.max_sin(len.min(e_len)) {
+                    continue;
+                }
+                // Where along the segment it is near the line of the other,
+                // and beside it.
+                let normal = Pt::new(-e.y, e.x) / e_len;
+                let near = between((a - p).dot(normal), (b - p).dot(normal), -self.tolerance, self.tolerance);
+                let beside = between((a - p).dot(e), (b - p).dot(e), 0.0, e_len * e_len);
+                if let (Some(near), Some(beside)) = (near, beside) {
+                    let (s, t) = (near.0.max(beside.0), near.1.min(beside.1));
+                    if s < t {
+                        covered.push((s, t));
+                    }
+                }
+            }
+        }
+        // Segments shorter than the tolerance are only dropped when covered.
+        if covered.is_empty() {
+            parts.push((0.0, 1.0));
+            return;
+        }
+        covered.sort_by(|x, y| x.0.total_cmp(&y.0));
+        let min_part = self.tolerance / len;
+        let mut from = 0.0;
+        for (s, t) in covered {
+            if s - from > min_part {
+                parts.push((from, s.min(1.0)));
+            }
+            from = f64::max(from, t);
+            if from >= 1.0 {
+                break;
+            }
+        }
+        if 1.0 - from > min_part {
+            parts.push((from, 1.0));
+        }
+    }
+
+    /// `path` without the parts of its straight segments drawn before, when
+    /// `editable`. Its straight segments are drawn after that.
+    fn trim(&mut self, path: &PathData, editable: bool, trimmed: &mut usize) -> PathData {
+        let mut out = PathData::new();
+        let mut parts = Vec::new();
+        let (mut start, mut at) = (Pt::zero(), Pt::zero());
+        let mut drawing = false; // `out` ends at `at`
+        let mut intact = true; // The subpath has not been cut
+        for seg in path.iter() {
+            let to = match *seg {
+                PathSegment::MoveTo { x, y } => {
+                    start = Pt::new(x, y);
+                    at = start;
+                    drawing = false;
+                    intact = true;
+                    continue;
+                }
+                PathSegment::CurveTo { x1, y1, x2, y2, x, y } => {
+                    if !drawing {
+                        out.push_move_to(at.x, at.y);
+                    }
+                    out.push_curve_to(x1, y1, x2, y2, x, y);
+                    at = Pt::new(x, y);
+                    drawing = true;
+                    continue;
+                }
+                PathSegment::LineTo { x, y } => Pt::new(x, y),
+                PathSegment::ClosePath => start,
+            };
+            let closing = matches!(*seg, PathSegment::ClosePath);
+
+            if at == to {
+                if closing && intact && drawing {
+                    out.push_close_path();
+                }
+                at = to;
+                continue;
+            }
+            parts.clear();
+            if editable {
+                self.uncovered(at, to, &mut parts);
+            } else {
+                parts.push((0.0, 1.0));
+            }
+            if parts != [(0.0, 1.0)] {
+                *trimmed += 1;
+                intact = false;
+            }
+            for &(s, t) in &parts {
+                let (p, q) = (at.lerp(to, s), at.lerp(to, t));
+                if !(drawing && s == 0.0) {
+                    out.push_move_to(p.x, p.y);
+                }
+                if closing && intact {
+                    out.push_close_path();
+                } else {
+                    out.push_line_to(q.x, q.y);
+                }
+                drawing = t == 1.0;
+            }
+            if parts.is_empty() {
+                drawing = false;
+            }
+            self.add(at, to);
+            at = to;
+        }
+        out
+    }
+}
+
+/// The interval of `[0, 1]` where the value going from `from` to `to`
+/// is between `lo` and `hi`.
+fn between(from: f64, to: f64, lo: f64, hi: f64) -> Option<(f64, f64)> {
+    let change = to - from;
+    if change == 0.0 {
+        return (lo..=hi).contains(&from).then_some((0.0, 1.0));
+    }
+    let (s, t) = ((lo - from) / change, (hi - from) / change);
+    let (s, t) = (s.min(t).max(0.0), s.max(t).min(1.0));
+    (s <= t).then_some((s, t))
+}
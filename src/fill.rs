@@ -0,0 +1,324 @@
+//! Points and hatching inside filled paths, for stippling, particle
+//! effects and pen plotters.
+
+use crate::rng::Rng;
+use crate::{flatten, Pt};
+use usvg::{FillRule, PathData, Transform};
+
+/// Pattern used to fill the inside of a filled path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Infill {
+    /// Points on a square grid with `spacing` between them.
+    Grid,
+    /// One point at a random position in every grid cell.
+    Jitter,
+    /// Random points no closer than `spacing` to each other.
+    Poisson,
+    /// Parallel lines `spacing` apart.
+    Hatch,
+    /// Two sets of parallel lines at right angles.
+    CrossHatch,
+    /// Loops offset inwards from the outline, `spacing` apart.
+    Concentric,
+    /// Like `Concentric`, but the loops are joined into a spiral.
+    Spiral,
+}
+
+impl Infill {
+    /// Whether the infill is made of paths rather than points.
+    pub fn is_path(self) -> bool {
+        !matches!(self, Infill::Grid | Infill::Jitter | Infill::Poisson)
+    }
+}
+
+impl std::str::FromStr for Infill {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Infill, String> {
+        match s {
+            "grid" => Ok(Infill::Grid),
+            "jitter" => Ok(Infill::Jitter),
+            "poisson" => Ok(Infill::Poisson),
+            "hatch" => Ok(Infill::Hatch),
+            "cross-hatch" => Ok(Infill::CrossHatch),
+            "concentric" => Ok(Infill::Concentric),
+            "spiral" => Ok(Infill::Spiral),
+            _ => Err(format!(
+                "unknown infill '{}', expected grid, jitter, poisson, hatch, cross-hatch, \
+                 concentric or spiral", s
+            )),
+        }
+    }
+}
+
+/// Flattened area of a filled path, every subpath is implicitly closed.
+pub struct Shape {
+    rings: Vec<Vec<Pt>>,
+    rule: FillRule,
+    pub min: Pt,
+    pub max: Pt,
+}
+
+impl Shape {
+    pub fn new(path: &PathData, rule: FillRule, accuracy: f64) -> Shape {
+        let rings: Vec<Vec<Pt>> = flatten(path, accuracy).into_iter()
+            .map(|(mut ring, _)| {
+                if let (Some(&first), Some(&last)) = (ring.first(), ring.last()) {
+                    if first != last {
+                        ring.push(first);
+                    }
+                }
+                ring
+            })
+            .filter(|ring| ring.len() > 2)
+            .collect();
+
+        let mut min = Pt::new(f64::INFINITY, f64::INFINITY);
+        let mut max = Pt::new(f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for &pt in rings.iter().flatten() {
+            min = min.min(pt);
+            max = max.max(pt);
+        }
+        Shape { rings, rule, min, max }
+    }
+
+    /// The shape mirrored along its diagonal, its `spans` run along the
+    /// columns of the original.
+    pub fn transposed(&self) -> Shape {
+        let swap = |pt: &Pt| Pt::new(pt.y, pt.x);
+        Shape {
+            rings: self.rings.iter().map(|ring| ring.iter().map(swap).collect()).collect(),
+            rule: self.rule,
+            min: swap(&self.min),
+            max: swap(&self.max),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rings.is_empty()
+    }
+
+    pub fn contains(&self, pt: Pt) -> bool {
+        self.is_inside(self.winding(pt))
+    }
+
+    fn winding(&self, pt: Pt) -> i32 {
+        self.rings.iter().map(|ring| winding(ring, pt)).sum()
+    }
+
+    /// Collects the parts of the horizontal line at `y` that are inside
+    /// the shape into `spans`, as sorted `(x_start, x_end)` pairs.
+    pub fn spans(&self, y: f64, spans: &mut Vec<(f64, f64)>) {
+        let mut crossings: Vec<(f64, i32)> = Vec::new();
+        for ring in &self.rings {
+            for edge in ring.windows(2) {
+                let (a, b) = (edge[0], edge[1]);
+                let dir = if a.y <= y && b.y > y {
+                    1
+                } else if b.y <= y && a.y > y {
+                    -1
+                } else {
+                    continue;
+                };
+                crossings.push((a.x + (y - a.y) * (b.x - a.x) / (b.y - a.y), dir));
+            }
+        }
+        crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        spans.clear();
+        let mut winding = 0;
+        let mut span_start = 0.0;
+        for (x, dir) in crossings {
+            let was_inside = self.is_inside(winding);
+            winding += dir;
+            match (was_inside, self.is_inside(winding)) {
+                (false, true) => span_start = x,
+                (true, false) if x > span_start => spans.push((span_start, x)),
+                _ => (),
+            }
+        }
+    }
+
+    fn is_inside(&self, winding: i32) -> bool {
+        match self.rule {
+            FillRule::NonZero => winding != 0,
+            FillRule::EvenOdd => winding % 2 != 0,
+        }
+    }
+}
+
+/// Winding number of the closed polyline `ring` around `pt`.
+pub fn winding(ring: &[Pt], pt: Pt) -> i32 {
+    let mut winding = 0;
+    for edge in ring.windows(2) {
+        let (a, b) = (edge[0], edge[1]);
+        let side = (b - a).cross(pt - a);
+        if a.y <= pt.y {
+            if b.y > pt.y && side > 0.0 {
+                winding += 1;
+            }
+        } else if b.y <= pt.y && side < 0.0 {
+            winding -= 1;
+        }
+    }
+    winding
+}
+
+/// Places points inside `shape` using the `infill` pattern, appending
+/// them to `out`. Grid based patterns are aligned to multiples of
+/// `spacing` so neighbouring shapes share the same grid.
+pub fn sample(shape: &Shape, infill: Infill, spacing: f64, rng: &mut Rng, out: &mut Vec<Pt>) {
+    if shape.is_empty() || spacing <= 0.0 {
+        return;
+    }
+    let first_row = (shape.min.y / spacing).floor() as i64;
+    let last_row = (shape.max.y / spacing).ceil() as i64;
+    match infill {
+        Infill::Grid => {
+            let mut spans = Vec::new();
+            for row in first_row..=last_row {
+                let y = row as f64 * spacing;
+                shape.spans(y, &mut spans);
+                for &(start, end) in &spans {
+                    let first_col = (start / spacing).ceil() as i64;
+                    let last_col = (end / spacing).floor() as i64;
+                    for col in first_col..=last_col {
+                        out.push(Pt::new(col as f64 * spacing, y));
+                    }
+                }
+            }
+        }
+        Infill::Jitter => {
+            let first_col = (shape.min.x / spacing).floor() as i64;
+            let last_col = (shape.max.x / spacing).ceil() as i64;
+            for row in first_row..last_row {
+                for col in first_col..last_col {
+                    let pt = Pt::new(
+                        rng.range(col as f64, col as f64 + 1.0) * spacing,
+                        rng.range(row as f64, row as f64 + 1.0) * spacing,
+                    );
+                    if shape.contains(pt) {
+                        out.push(pt);
+                    }
+                }
+            }
+        }
+        Infill::Poisson => poisson(shape, spacing, rng, out),
+        Infill::Hatch | Infill::CrossHatch | Infill::Concentric | Infill::Spiral => (),
+    }
+}
+
+/// Appends hatch lines `spacing` apart covering the inside of `path` to
+/// `out`, one subpath per line. The `angle` is in degrees counter-clockwise
+/// from the x axis, as the drawing is seen. Every other line is reversed
+/// so a plotter can work back and forth.
+pub fn hatch(
+    path: &PathData,
+    rule: FillRule,
+    accuracy: f64,
+    spacing: f64,
+    angle: f64,
+    out: &mut PathData,
+) {
+    // Rotate the shape so the hatch lines are horizontal, svg y is down.
+    let mut rotated = path.clone();
+    rotated.transform(Transform::new_rotate(angle));
+    let shape = Shape::new(&rotated, rule, accuracy);
+    if shape.is_empty() || spacing <= 0.0 {
+        return;
+    }
+    let back = Transform::new_rotate(-angle);
+
+    let first_row = (shape.min.y / spacing).ceil() as i64;
+    let last_row = (shape.max.y / spacing).floor() as i64;
+    let mut spans = Vec::new();
+    let mut forward = true;
+    for row in first_row..=last_row {
+        let y = row as f64 * spacing;
+        shape.spans(y, &mut spans);
+        if !forward {
+            spans.reverse();
+        }
+        for &(start, end) in &spans {
+            let (start, end) = if forward { (start, end) } else { (end, start) };
+            let (x, y0) = back.apply(start, y);
+            out.push_move_to(x, y0);
+            let (x, y0) = back.apply(end, y);
+            out.push_line_to(x, y0);
+        }
+        if !spans.is_empty() {
+            forward = !forward;
+        }
+    }
+}
+
+/// Bridson's poisson disk sampling, restricted to the inside of `shape`.
+/// Growth is restarted from every jittered grid cell so that every
+/// disconnected part of the shape gets filled.
+fn poisson(shape: &Shape, radius: f64, rng: &mut Rng, out: &mut Vec<Pt>) {
+    const ATTEMPTS: usize = 30;
+    let cell = radius / std::f64::consts::SQRT_2;
+    let cols = ((shape.max.x - shape.min.x) / cell).ceil() as usize + 1;
+    let rows = ((shape.max.y - shape.min.y) / cell).ceil() as usize + 1;
+    let mut grid: Vec<Option<usize>> = vec![None; cols * rows];
+    let mut points: Vec<Pt> = Vec::new();
+    let mut active: Vec<usize> = Vec::new();
+
+    let cell_of = |pt: Pt| -> (usize, usize) {
+        (((pt.x - shape.min.x) / cell) as usize, ((pt.y - shape.min.y) / cell) as usize)
+    };
+    let is_free = |points: &[Pt], grid: &[Option<usize>], pt: Pt| -> bool {
+        if pt.x < shape.min.x || pt.y < shape.min.y || pt.x > shape.max.x || pt.y > shape.max.y {
+            return false;
+        }
+        let (cx, cy) = cell_of(pt);
+        for y in cy.saturating_sub(2)..(cy + 3).min(rows) {
+            for x in cx.saturating_sub(2)..(cx + 3).min(cols) {
+                if let Some(i) = grid[y * cols + x] {
+                    if (points[i] - pt).square_length() < radius * radius {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    };
+
+    for seed_row in 0..rows {
+        for seed_col in 0..cols {
+            let seed = shape.min + Pt::new(
+                (seed_col as f64 + rng.next_f64()) * cell,
+                (seed_row as f64 + rng.next_f64()) * cell,
+            );
+            if !shape.contains(seed) || !is_free(&points, &grid, seed) {
+                continue;
+            }
+            let (cx, cy) = cell_of(seed);
+            grid[cy * cols + cx] = Some(points.len());
+            active.push(points.len());
+            points.push(seed);
+
+            while !active.is_empty() {
+                let slot = (rng.next_u64() % active.len() as u64) as usize;
+                let center = points[active[slot]];
+                let mut found = false;
+                for _ in 0..ATTEMPTS {
+                    let angle = rng.range(0.0, std::f64::consts::TAU);
+                    let dist = rng.range(radius, 2.0 * radius);
+                    let pt = center + Pt::new(angle.cos(), angle.sin()) * dist;
+                    if is_free(&points, &grid, pt) && shape.contains(pt) {
+                        let (cx, cy) = cell_of(pt);
+                        grid[cy * cols + cx] = Some(points.len());
+                        active.push(points.len());
+                        points.push(pt);
+                        found = true;
+                        break;
+                    }
+                }
+                if !found {
+                    active.swap_remove(slot);
+                }
+            }
+        }
+    }
+    out.extend(points);
+}
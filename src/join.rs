@@ -0,0 +1,148 @@
+//! Joining of paths whose ends meet into longer paths, so they are drawn
+//! without lifting the pen, and sampled as one.
+
+use crate::order::{self, Entry};
+use crate::Pt;
+use std::collections::HashMap;
+use usvg::{PathData, PathSegment};
+
+/// The ends of an open path.
+fn ends(path: &PathData) -> Option<(Pt, Pt)> {
+    let mut subpaths = path.subpaths();
+    let subpath = subpaths.next()?;
+    if subpaths.next().is_some() {
+        return None;
+    }
+    let point = |seg: &PathSegment| match *seg {
+        PathSegment::MoveTo { x, y } | PathSegment::LineTo { x, y } => Some(Pt::new(x, y)),
+        PathSegment::CurveTo { x, y, .. } => Some(Pt::new(x, y)),
+        PathSegment::ClosePath => None,
+    };
+    Some((point(subpath.first()?)?, point(subpath.last()?)?))
+}
+
+/// Ends of the paths bucketed by position, so the ends near a point are
+/// found without looking at all of them.
+struct Grid {
+    cell: f64,
+    tolerance: f64,
+    cells: HashMap<(i64, i64), Vec<(usize, bool)>>,
+}
+
+impl Grid {
+    fn key(&self, pt: Pt) -> (i64, i64) {
+        ((pt.x / self.cell).floor() as i64, (pt.y / self.cell).floor() as i64)
+    }
+
+    /// An unused path with an end within the tolerance of `pt`, and
+    /// whether it is its last point.
+    fn find(&self, pt: Pt, ends: &[Option<(Pt, Pt)>], used: &[bool]) -> Option<(usize, bool)> {
+        let (cx, cy) = self.key(pt);
+        let mut best: Option<(f64, (usize, bool))> = None;
+        for x in cx - 1..=cx + 1 {
+            for y in cy - 1..=cy + 1 {
+                for &(index, last) in self.cells.get(&(x, y)).into_iter().flatten() {
+                    if used[index] {
+                        continue;
+                    }
+                    let (start, end) = ends[index].unwrap_or_default();
+                    let dist = (if last { end } else { start } - pt).length();
+                    if dist <= self.tolerance && best.map_or(true, |(d, found)| (dist, index) < (d, found.0)) {
+                        best = Some((dist, (index, last)));
+                    }
+                }
+            }
+        }
+        best.map(|(_, found)| found)
+    }
+}
+
+/// Joins the open paths of `paths` for which `joinable` is true when
+/// their ends are within `tolerance`, reversing them where needed. Paths
+/// joined into a loop are closed. The joined paths take the place of the
+/// first of them in `paths`, and every path returned comes with the
+/// index of that path.
+pub fn join(mut paths: Vec<PathData>, joinable: impl Fn(usize) -> bool, tolerance: f64) -> (Vec<PathData>, Vec<usize>) {
+    let ends: Vec<Option<(Pt, Pt)>> = paths.iter().enumerate()
+        .map(|(index, path)| ends(path).filter(|_| joinable(index)))
+        .collect();
+    let mut grid = Grid {
+        cell: if tolerance > 0.0 { tolerance } else { 1.0 },
+        tolerance,
+        cells: HashMap::new(),
+    };
+    for (index, &(start, end)) in ends.iter().enumerate().filter_map(|(i, ends)| Some((i, ends.as_ref()?))) {
+        grid.cells.entry(grid.key(start)).or_default().push((index, false));
+        grid.cells.entry(grid.key(end)).or_default().push((index, true));
+    }
+
+    let mut used = vec![false; paths.len()];
+    let mut joined = Vec::with_capacity(paths.len());
+    let mut sources = Vec::with_capacity(paths.len());
+    for index in 0..paths.len() {
+        if used[index] {
+            continue;
+        }
+        used[index] = true;
+        let Some((start, end)) = ends[index] else {
+            joined.push(std::mem::take(&mut paths[index]));
+            sources.push(index);
+            continue;
+        };
+
+        // Grow the chain from its end, then from its start by growing the
+        // reversed chain, and turn it back.
+        let mut chain = vec![(index, false)];
+        let (mut first, mut last) = (start, end);
+        for _ in 0..2 {
+            while let Some((next, reversed)) = grid.find(last, &ends, &used) {
+                used[next] = true;
+                let (start, end) = ends[next].unwrap_or_default();
+                last = if reversed { start } else { end };
+                chain.push((next, reversed));
+            }
+            chain.reverse();
+            for piece in &mut chain {
+                piece.1 = !piece.1;
+            }
+            std::mem::swap(&mut first, &mut last);
+        }
+
+        if chain.len() == 1 {
+            joined.push(std::mem::take(&mut paths[index]));
+        } else {
+            let mut path = PathData::new();
+            for &(piece, reversed) in &chain {
+                let piece = order::apply(&paths[piece], if reversed { Entry::Reversed } else { Entry::Forward });
+                for seg in piece.iter() {
+                    match *seg {
+                        // Bridge the gap left within the tolerance.
+                        PathSegment::MoveTo { x, y } if !path.is_empty() => {
+                            if end_of(&path) != Pt::new(x, y) {
+                                path.push_line_to(x, y);
+                            }
+                        }
+                        seg => path.push(seg),
+                    }
+                }
+            }
+            if (last - first).length() <= tolerance {
+                // The ClosePath draws the line back to the start.
+                if last == first && matches!(path.last(), Some(PathSegment::LineTo { .. })) {
+                    path.pop();
+                }
+                path.push_close_path();
+            }
+            joined.push(path);
+        }
+        sources.push(index);
+    }
+    (joined, sources)
+}
+
+fn end_of(path: &PathData) -> Pt {
+    match path.last() {
+        Some(&PathSegment::MoveTo { x, y } | &PathSegment::LineTo { x, y } | &PathSegment::CurveTo { x, y, .. }) => Pt::new(x, y),
+        _ => Pt::zero(),
+    }
+}
@@ -0,0 +1,147 @@
+//! Direction and start point of closed subpaths, so loops are drawn
+//! consistently.
+
+use crate::fill::winding;
+use crate::order::{edges, push_subpath, reverse, rotate, Edge};
+use crate::Pt;
+use lyon_geom::cubic_bezier::CubicBezierSegment;
+use usvg::PathData;
+
+/// Points used for every curve when measuring loops.
+const CURVE_STEPS: usize = 16;
+
+/// Direction of closed subpaths, as the drawing is seen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Cw,
+    Ccw,
+    /// Clockwise, holes inside other loops of the path counter-clockwise.
+    CwOuter,
+    /// Counter-clockwise, holes clockwise.
+    CcwOuter,
+}
+
+impl std::str::FromStr for Orientation {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Orientation, String> {
+        match s {
+            "cw" => Ok(Orientation::Cw),
+            "ccw" => Ok(Orientation::Ccw),
+            "cw-outer" => Ok(Orientation::CwOuter),
+            "ccw-outer" => Ok(Orientation::CcwOuter),
+            _ => Err(format!("unknown orientation '{}', expected cw, ccw, cw-outer or ccw-outer", s)),
+        }
+    }
+}
+
+/// Vertex closed subpaths start from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Start {
+    Leftmost,
+    Topmost,
+    /// Closest to where the pen is.
+    Nearest,
+    /// Closest to a point in output coordinates.
+    Point(Pt),
+}
+
+impl std::str::FromStr for Start {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Start, String> {
+        match s {
+            "leftmost" => Ok(Start::Leftmost),
+            "topmost" => Ok(Start::Topmost),
+            "nearest" => Ok(Start::Nearest),
+            _ => match s.split_once(',').map(|(x, y)| (x.trim().parse(), y.trim().parse())) {
+                Some((Ok(x), Ok(y))) => Ok(Start::Point(Pt::new(x, y))),
+                _ => Err(format!("unknown start '{}', expected leftmost, topmost, nearest or x,y", s)),
+            },
+        }
+    }
+}
+
+/// The subpath from `start` along `edges` as a polyline.
+fn polyline(start: Pt, edges: &[Edge]) -> Vec<Pt> {
+    let mut line = vec![start];
+    for edge in edges {
+        if let Some((c1, c2)) = edge.ctrl {
+            let from = *line.last().unwrap();
+            let bez = CubicBezierSegment {
+                from: from.to_point(),
+                ctrl1: c1.to_point(),
+                ctrl2: c2.to_point(),
+                to: edge.to.to_point(),
+            };
+            line.extend((1..=CURVE_STEPS).map(|i| bez.sample(i as f64 / CURVE_STEPS as f64).to_vector()));
+        } else {
+            line.push(edge.to);
+        }
+    }
+    line
+}
+
+/// Twice the signed area of the closed polyline `ring`, positive when it
+/// runs clockwise as seen, with the svg y axis pointing down.
+fn area(ring: &[Pt]) -> f64 {
+    ring.windows(2).map(|w| w[0].cross(w[1])).sum()
+}
+
+/// `path` with its closed subpaths turned to `orientation` and started
+/// from the vertex picked by `start`. `pen` is where the previous subpath
+/// ended, and is moved to the end of the last subpath of `path`. `map`
+/// gives the output coordinates of a point.
+pub fn arrange(
+    path: &PathData,
+    orientation: Option<Orientation>,
+    start: Option<Start>,
+    pen: &mut Pt,
+    map: impl Fn(Pt) -> Pt,
+) -> PathData {
+    let subpaths: Vec<_> = path.subpaths().map(|subpath| edges(subpath.iter())).collect();
+    let rings: Vec<Option<Vec<Pt>>> = subpaths.iter()
+        .map(|(start, edges, closed)| closed.then(|| polyline(*start, edges)))
+        .collect();
+
+    let mut out = PathData::new();
+    for (i, (mut from, mut edges, closed)) in subpaths.into_iter().enumerate() {
+        let Some(ring) = &rings[i] else {
+            push_subpath(&mut out, from, &edges, false);
+            *pen = edges.last().map_or(from, |edge| edge.to);
+            continue;
+        };
+
+        if let Some(orientation) = orientation {
+            let hole = match orientation {
+                Orientation::CwOuter | Orientation::CcwOuter => {
+                    let inside = rings.iter().enumerate()
+                        .filter(|&(j, other)| j != i && other.as_ref().is_some_and(|other| winding(other, ring[0]) != 0))
+                        .count();
+                    inside % 2 == 1
+                }
+                _ => false,
+            };
+            let cw = matches!(orientation, Orientation::Cw | Orientation::CwOuter) != hole;
+            let area = area(ring);
+            if area != 0.0 && (area > 0.0) != cw {
+                (from, edges) = reverse(from, &edges);
+            }
+        }
+
+        if let Some(start) = start {
+            let vertices = std::iter::once(from).chain(edges[..edges.len() - 1].iter().map(|edge| edge.to));
+            let key = |pt: Pt| match start {
+                Start::Leftmost => (pt.x, pt.y),
+                Start::Topmost => (pt.y, pt.x),
+                Start::Nearest => ((pt - *pen).length(), 0.0),
+                Start::Point(target) => ((map(pt) - target).length(), 0.0),
+            };
+            let k = vertices.enumerate()
+                .min_by(|(_, a), (_, b)| key(*a).partial_cmp(&key(*b)).unwrap_or(std::cmp::Ordering::Equal))
+                .map_or(0, |(k, _)| k);
+            (from, edges) = rotate(from, &edges, k);
+        }
+        push_subpath(&mut out, from, &edges, closed);
+        *pen = from;
+    }
+    out
+}
@@ -4,7 +4,21 @@ use std::fs::File;
 use std::io::prelude::*;
 use std::io;
 use kurbo::common::solve_quadratic; // usvg already uses kurbo
-use usvg::{NodeKind, PathSegment, Tree, TransformedPath, NodeExt};
+use usvg::{NodeKind, PathSegment, Tree, NodeExt};
+mod dedup;
+mod fill;
+mod join;
+mod loops;
+mod offset;
+mod order;
+mod rng;
+mod scatter;
+mod select;
+mod simplify;
+mod stroke;
+
+use fill::Infill;
+use simplify::Simplify;
 type Ret<T> = Result<T, Box<dyn std::error::Error>>;
 type Pt = Vector2D<f64, lyon_geom::euclid::UnknownUnit>;
 
@@ -22,7 +36,145 @@ struct Opt {
 
     points: u64,
 
-    var_distance: bool,
+    /// How the `points` budget is split between paths.
+    allocation: Allocation,
+
+    /// Smallest number of points per path for `Allocation::Minimum`.
+    min_points: u64,
+
+    /// How points are distributed along the paths.
+    sampling: Sampling,
+
+    /// Pattern of points placed inside filled paths.
+    infill: Option<Infill>,
+
+    /// Distance between the infill points, defaults to `distance`.
+    infill_spacing: Option<f64>,
+    infill_spacing_unit: Option<Unit>,
+
+    /// Angle of the hatch lines in degrees.
+    hatch_angle: f64,
+
+    /// Skip the outline of paths that are filled but not stroked.
+    no_outline: bool,
+
+    /// Trace the outline of strokes, using their width, joins and caps,
+    /// rather than their center line.
+    stroke_outline: bool,
+
+    /// Split dashed strokes into a subpath per dash.
+    dashes: bool,
+
+    /// Write an empty line between subpaths.
+    blank_lines: bool,
+
+    /// Extra values written after the coordinates of every point.
+    columns: Columns,
+    /// Unit of the length part of `columns.speed`.
+    speed_unit: Option<Unit>,
+
+    /// Seed for the random number generator.
+    seed: u64,
+
+    /// Adjust the spacing of closed subpaths to divide their perimeter
+    /// evenly, without repeating the start point.
+    seamless: bool,
+
+    /// Drop points that barely change the shape of their subpath.
+    simplify: Option<Simplify>,
+    /// Unit of the `Simplify::Rdp` tolerance.
+    rdp_unit: Option<Unit>,
+
+    /// Keep the svg direction of the y axis, pointing down.
+    y_down: bool,
+
+    /// Point written as `0 0`, defaults to the viewBox corner the y axis
+    /// starts from.
+    origin: Option<Origin>,
+
+    /// Box the output is scaled and moved into, two opposite corners.
+    fit: Option<[Length; 4]>,
+
+    /// Fit the viewBox into `fit` rather than the bounds of the paths.
+    fit_view_box: bool,
+
+    /// Scale x and y separately to fill `fit`.
+    stretch: bool,
+
+    /// Space left inside each side of `fit`.
+    margin: f64,
+    margin_unit: Option<Unit>,
+
+    /// Scale of the output, after `fit`.
+    scale: Option<Pt>,
+
+    /// Scale the paths before sampling, so distances are in output units.
+    scale_first: bool,
+
+    /// Applied to the whole svg in order, on top of the transforms inside
+    /// it.
+    transforms: Vec<TransformArg>,
+
+    /// Units of the output, and of distances without units. Svg user
+    /// units, those of the viewBox, when not set.
+    units: Option<Unit>,
+
+    /// Units `distance` and `accuracy` were given in, when not `units`.
+    distance_unit: Option<Unit>,
+    accuracy_unit: Option<Unit>,
+
+    /// Resolution used for units in the svg and for `Unit::Px`.
+    dpi: Option<f64>,
+
+    /// Write every value with this many decimals.
+    precision: Option<usize>,
+
+    /// Snap the output coordinates to multiples of this step.
+    quantize: Option<f64>,
+    quantize_unit: Option<Unit>,
+
+    /// Drop points that are the same as the point before them.
+    skip_repeats: bool,
+
+    /// Ids of the paths, or groups around them, to keep or leave out.
+    selection: select::Selection,
+
+    /// Labels of the Inkscape layers to keep.
+    layers: Vec<String>,
+
+    /// Which paths are converted, besides the selection.
+    visibility: Visibility,
+
+    /// Drop paths and parts of lines that run this close to ones drawn
+    /// before.
+    dedup: Option<f64>,
+    dedup_unit: Option<Unit>,
+
+    /// Join paths whose ends are this close.
+    join: Option<f64>,
+    join_unit: Option<Unit>,
+
+    /// Direction closed subpaths are turned to.
+    orientation: Option<loops::Orientation>,
+
+    /// Vertex closed subpaths start from.
+    start: Option<loops::Start>,
+
+    /// Group the paths by color, for a pen each.
+    pen_color: Option<PenColor>,
+
+    /// Mark each color group in the output, rather than writing a file
+    /// per color.
+    pen_markers: bool,
+
+    /// Write every path or group to a file of its own.
+    split: Option<Split>,
+
+    /// Names of the split files, from `{stem}`, `{id}` and `{index}`.
+    split_template: String,
+
+    /// Reorder the paths to reduce the travel between them.
+    order: Option<order::Ordering>,
 
     /// Input SVG file, stdin if not present
     //  #[structopt(parse(from_os_str))]
@@ -33,25 +185,469 @@ struct Opt {
     output: Option<String>,
 }
 
+/// Optional values written after `X Y`, in the order of the fields.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct Columns {
+    /// Unit tangent `TX TY`, in the direction of the path.
+    tangent: bool,
+    /// Unit normal `NX NY`, the tangent turned counter-clockwise.
+    normal: bool,
+    /// Signed curvature `K`, positive when turning counter-clockwise.
+    curvature: bool,
+    /// Distance along the path `S` and along the whole document `D`.
+    arc_length: bool,
+    /// Drawing speed for the time `T` a point is reached, `D / speed`.
+    speed: Option<f64>,
+}
+
+impl Columns {
+    /// Number of values written after `X Y`.
+    fn len(self) -> usize {
+        2 * self.tangent as usize + 2 * self.normal as usize + self.curvature as usize
+            + 2 * self.arc_length as usize + self.speed.is_some() as usize
+    }
+
+    /// Whether the distance along the paths is written.
+    fn needs_dist(self) -> bool {
+        self.arc_length || self.speed.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unit {
+    Px,
+    Mm,
+    In,
+    Pt,
+}
+
+impl Unit {
+    fn in_px(self, dpi: f64) -> f64 {
+        match self {
+            Unit::Px => 1.0,
+            Unit::Mm => dpi / 25.4,
+            Unit::In => dpi,
+            Unit::Pt => dpi / 72.0,
+        }
+    }
+}
+
+impl std::str::FromStr for Unit {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Unit, String> {
+        match s {
+            "px" => Ok(Unit::Px),
+            "mm" => Ok(Unit::Mm),
+            "in" => Ok(Unit::In),
+            "pt" => Ok(Unit::Pt),
+            _ => Err(format!("unknown unit '{}', expected mm, in, px or pt", s)),
+        }
+    }
+}
+
+/// A number with the unit it was given in, if any.
+type Length = (f64, Option<Unit>);
+
+/// A transform given on the command line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TransformArg {
+    Matrix(Transform),
+    /// Degrees clockwise, around the origin or a center.
+    Rotate(f64, Option<[Length; 2]>),
+    Translate([Length; 2]),
+}
+
+/// Parses a number followed by an optional unit, such as `0.5mm`.
+fn parse_length(arg: &str, value: &str) -> Ret<Length> {
+    let split = value.trim_end_matches(|c: char| c.is_ascii_alphabetic()).len();
+    let (number, unit) = value.split_at(split);
+    let number = number.parse::<f64>().map_err(|err| {
+        format!("{err}: Invalid value '{}' <f64>[mm|in|px|pt]", arg)
+    })?;
+    let unit = if unit.is_empty() { None } else { Some(unit.parse::<Unit>()?) };
+    Ok((number, unit))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Origin {
+    /// The min-x, min-y corner of the viewBox.
+    TopLeft,
+    BottomLeft,
+    /// The center of the viewBox.
+    Center,
+    /// The corner of the bounding box of all paths with the smallest
+    /// output coordinates.
+    Bounds,
+}
+
+impl std::str::FromStr for Origin {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Origin, String> {
+        match s {
+            "top-left" => Ok(Origin::TopLeft),
+            "bottom-left" => Ok(Origin::BottomLeft),
+            "center" => Ok(Origin::Center),
+            "bounds" => Ok(Origin::Bounds),
+            _ => Err(format!(
+                "unknown origin '{}', expected top-left, bottom-left, center or bounds", s
+            )),
+        }
+    }
+}
+
+/// Maps svg coordinates to output coordinates.
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    origin: Pt, // In svg coordinates
+    y_up: bool, // Flip the svg y axis
+    scale: Pt,  // Applied after moving the origin and flipping
+    offset: Pt, // Added last
+}
+
+impl Frame {
+    fn apply(&self, pt: Pt) -> Pt {
+        self.offset + self.vector(pt - self.origin)
+    }
+
+    /// Maps a direction or derivative, without the translation.
+    fn vector(&self, v: Pt) -> Pt {
+        let y = if self.y_up { -v.y } else { v.y };
+        Pt::new(v.x * self.scale.x, y * self.scale.y)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Sampling {
+    /// Points are placed `distance` apart, carrying the remainder over
+    /// from one segment to the next.
+    #[default]
+    Distance,
+    /// Every segment is divided into a whole number of equal parts
+    /// close to `distance`, so the original endpoints are kept.
+    Segments,
+    /// Points are placed uniformly at random along the paths.
+    Random,
+    /// Random points kept apart from each other, for an average spacing
+    /// close to `distance`.
+    BlueNoise,
+}
+
+impl Sampling {
+    fn is_random(self) -> bool {
+        matches!(self, Sampling::Random | Sampling::BlueNoise)
+    }
+}
+
+impl std::str::FromStr for Sampling {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Sampling, String> {
+        match s {
+            "distance" => Ok(Sampling::Distance),
+            "segments" => Ok(Sampling::Segments),
+            "random" => Ok(Sampling::Random),
+            "blue-noise" => Ok(Sampling::BlueNoise),
+            _ => Err(format!(
+                "unknown sampling '{}', expected distance, segments, random or blue-noise", s
+            )),
+        }
+    }
+}
+
+/// Paint paths are grouped by, one pen for each color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PenColor {
+    Stroke,
+    Fill,
+}
+
+impl std::str::FromStr for PenColor {
+    type Err = String;
+    fn from_str(s: &str) -> Result<PenColor, String> {
+        match s {
+            "stroke" => Ok(PenColor::Stroke),
+            "fill" => Ok(PenColor::Fill),
+            _ => Err(format!("unknown paint '{}', expected stroke or fill", s)),
+        }
+    }
+}
+
+/// Runs `edit` on the paths of every group on its own, so paths of
+/// different groups are never merged. `sources` gives the svg path of
+/// every path and `keys` the group of every svg path. `edit` gets the
+/// paths of a group with their svg paths, and returns the paths left with
+/// the index in its input of the path each comes from.
+fn per_group(
+    paths: Vec<PathData>,
+    sources: Vec<usize>,
+    keys: &[&Option<String>],
+    mut edit: impl FnMut(Vec<PathData>, &[usize]) -> (Vec<PathData>, Vec<usize>),
+) -> (Vec<PathData>, Vec<usize>) {
+    let mut groups: Vec<(&Option<String>, Vec<PathData>, Vec<usize>)> = Vec::new();
+    for (path, source) in paths.into_iter().zip(sources) {
+        let key = keys[source];
+        match groups.iter_mut().find(|(group, _, _)| *group == key) {
+            Some((_, paths, sources)) => {
+                paths.push(path);
+                sources.push(source);
+            }
+            None => groups.push((key, vec![path], vec![source])),
+        }
+    }
+    let (mut paths, mut sources) = (Vec::new(), Vec::new());
+    for (_, group, group_sources) in groups {
+        let (left, from) = edit(group, &group_sources);
+        paths.extend(left);
+        sources.extend(from.iter().map(|&i| group_sources[i]));
+    }
+    (paths, sources)
+}
+
+/// Where the stops of a group are written.
+enum Header {
+    /// After a `# <text>` line in the output.
+    Pen(String),
+    /// To a file of their own.
+    File(std::path::PathBuf),
+}
+
+/// The group of every svg path, by what tells the groups apart, and its
+/// name, a color or the id of a part. Without --split or --by-color all
+/// paths are in one group, with no key.
+fn group_keys(opt: &Opt, svg_paths: &[SvgPath]) -> Vec<(Option<String>, String)> {
+    svg_paths.iter().enumerate().map(|(i, svg_path)| {
+        if let Some(split) = opt.split {
+            let id = split_id(&svg_path.node, split);
+            let name = if !id.is_empty() {
+                id.clone()
+            } else if split == Split::Path {
+                "path".to_string()
+            } else {
+                "group".to_string()
+            };
+            // Parts without an id can't be told apart.
+            let key = if id.is_empty() { format!("#{}", i) } else { id };
+            (Some(key), name)
+        } else if let Some(pen_color) = opt.pen_color {
+            let color = paint_name(match pen_color {
+                PenColor::Stroke => svg_path.stroke.as_ref().map(|stroke| &stroke.paint),
+                PenColor::Fill => svg_path.fill.as_ref().map(|fill| &fill.paint),
+            });
+            (Some(color.clone()), color)
+        } else {
+            (None, String::new())
+        }
+    }).collect()
+}
+
+/// Where the group `index` named `name` is written. Split files are named
+/// after the output, or the input.
+fn group_header(opt: &Opt, index: usize, name: &str) -> Header {
+    if opt.split.is_some() {
+        let base = std::path::Path::new(opt.output.as_ref().or(opt.input.as_ref()).map_or("", |s| s));
+        Header::File(base.with_file_name(opt.split_template
+            .replace("{stem}", &base.file_stem().unwrap_or_default().to_string_lossy())
+            .replace("{id}", &file_safe(name))
+            .replace("{index}", &index.to_string())))
+    } else if let Some(output) = opt.output.as_ref().filter(|_| file_per_group(opt)) {
+        Header::File(output_name(output, name))
+    } else {
+        Header::Pen(format!("pen {} {}", index + 1, name))
+    }
+}
+
+/// A path to write, by its index, and the direction it is drawn in.
+type Stop = (usize, order::Entry);
+
+/// Sorts `paths` into the groups of `parts`, by the svg path in `sources`
+/// each comes from, in the order the groups first appear, and orders
+/// every group on its own. Returns the paths in the order they are
+/// written with the direction they are drawn in, and where the stops of
+/// every group start with its header.
+fn plan_groups(
+    opt: &Opt,
+    paths: &[PathData],
+    sources: &[usize],
+    parts: &[(Option<String>, String)],
+    frame: &Frame,
+) -> (Vec<Stop>, Vec<(usize, Header)>) {
+    let mut groups: Vec<(&Option<String>, &String, Vec<usize>)> = Vec::new();
+    for (i, &source) in sources.iter().enumerate() {
+        let (key, name) = &parts[source];
+        match groups.iter_mut().find(|(group, _, _)| *group == key) {
+            Some((_, _, group)) => group.push(i),
+            None => groups.push((key, name, vec![i])),
+        }
+    }
+
+    let mut stops = Vec::with_capacity(paths.len());
+    let mut headers = Vec::new();
+    let (mut travel_before, mut travel_after) = (0.0, 0.0);
+    for (index, (key, name, group)) in groups.into_iter().enumerate() {
+        if key.is_some() {
+            headers.push((stops.len(), group_header(opt, index, name)));
+        }
+        if let Some(ordering) = opt.order {
+            let group_paths: Vec<&PathData> = group.iter().map(|&i| &paths[i]).collect();
+            let plan = order::plan(&group_paths, ordering, Pt::zero(), |pt| frame.apply(pt));
+            travel_before += plan.travel_before;
+            travel_after += plan.travel_after;
+            stops.extend(plan.stops.into_iter().map(|(k, entry)| (group[k], entry)));
+        } else {
+            stops.extend(group.into_iter().map(|i| (i, order::Entry::Forward)));
+        }
+    }
+    if opt.order.is_some() {
+        eprintln!("pen-up travel: {} before ordering, {} after", travel_before, travel_after);
+    }
+    (stops, headers)
+}
+
+/// Part of the svg written to a file of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Split {
+    Path,
+    /// The outermost group with an id, such as an Inkscape layer.
+    Group,
+}
+
+impl std::str::FromStr for Split {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Split, String> {
+        match s {
+            "path" => Ok(Split::Path),
+            "group" => Ok(Split::Group),
+            _ => Err(format!("unknown split '{}', expected path or group", s)),
+        }
+    }
+}
+
+/// Id of the part of the svg `node` is written with, empty when it has
+/// none. Paths outside any group with an id are parts of their own.
+fn split_id(node: &usvg::Node, split: Split) -> String {
+    let group = match split {
+        Split::Path => None,
+        Split::Group => node.ancestors().skip(1)
+            .filter(|node| matches!(*node.borrow(), NodeKind::Group(_)) && !node.id().is_empty())
+            .last(),
+    };
+    group.map_or_else(|| node.id().to_string(), |group| group.id().to_string())
+}
+
+/// Name of a paint, `#rrggbb` for colors, the id of gradients and
+/// patterns, and `none` when there is no paint.
+fn paint_name(paint: Option<&usvg::Paint>) -> String {
+    match paint {
+        Some(usvg::Paint::Color(c)) => format!("#{:02x}{:02x}{:02x}", c.red, c.green, c.blue),
+        Some(usvg::Paint::Link(id)) => id.clone(),
+        None => "none".to_string(),
+    }
+}
+
+/// `name` with the characters that are not safe in file names replaced,
+/// and without the `#` of colors.
+fn file_safe(name: &str) -> String {
+    name.trim_start_matches('#').chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// `output` with `_name` added to the file name, before the extension.
+fn output_name(output: &str, name: &str) -> std::path::PathBuf {
+    let path = std::path::Path::new(output);
+    let name = file_safe(name);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file = match path.extension() {
+        Some(ext) => format!("{}_{}.{}", stem, name, ext.to_string_lossy()),
+        None => format!("{}_{}", stem, name),
+    };
+    path.with_file_name(file)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Allocation {
+    /// Every path uses the same distance, so points are proportional to length.
+    #[default]
+    Length,
+    /// Like `Length`, but every path gets at least `min_points` points.
+    Minimum,
+    /// Every path gets the same number of points.
+    Equal,
+}
+
+impl std::str::FromStr for Allocation {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Allocation, String> {
+        match s {
+            "length" => Ok(Allocation::Length),
+            "minimum" => Ok(Allocation::Minimum),
+            "equal" => Ok(Allocation::Equal),
+            _ => Err(format!("unknown allocation '{}', expected length, minimum or equal", s)),
+        }
+    }
+}
+
 fn print_usage() {
     println!(
-        "{}",
         r#"svg2pts 0.1.5
 Converts all paths in a svg to a list of points. Will ignore paths
-with no stroke or fill. Output is a sequence of points, `X Y\n`. 
+with no stroke or fill, unless told otherwise with --unpainted. Output is a sequence of points, `X Y\n`,
+optionally followed by the columns `TX TY NX NY K S D T` in that order.
 
 USAGE:
     svg2pts [OPTIONS] [ <input> [ <output> ] ]
 
 FLAGS:
     -h, --help       Prints help information
+    -b, --blank-lines
+                     Write an empty line between subpaths, so tools like gnuplot
+                     draw them unconnected.
+        --dashes     Split dashed strokes into a subpath per dash, following their
+                     stroke-dasharray and stroke-dashoffset.
+        --no-outline Skip the outline of paths that are filled but not stroked,
+                     leaving only their infill.
+        --stroke-outline
+                     Replace stroked paths by the outline of their stroke, using the
+                     stroke width, line joins, miter limit and caps.
+        --stretch    Scale x and y separately to fill the --fit box.
+        --fit-view-box
+                     Fit the viewBox into the --fit box, rather than the bounds of
+                     the paths.
+        --scale-first
+                     Apply --fit and --scale to the paths before sampling, so
+                     distances, accuracy and the --arc-length columns are in output
+                     units rather than those of the svg.
+        --skip-repeats
+                     Drop points written the same as the point before them, such as
+                     after --quantize or --precision.
+        --skip-invisible
+                     Leave out paths that are not seen: with visibility hidden,
+                     opacity 0 on them or a group around them, or transparent
+                     fill and stroke.
+        --report-skipped
+                     Print every path left out on stderr, and why.
+        --pen-markers
+                     With --by-color, write every color to the same output, each
+                     after a `# pen <n> <color>` line.
+        --y-down     Keep the y axis of the svg pointing down, rather than up.
+        --seamless   Space the points of closed subpaths evenly all the way around,
+                     the start point is not repeated at the end of the loop.
+        --tangents   Add the unit tangent of the path at every point, `TX TY`.
+        --normals    Add the unit normal at every point, `NX NY`, the tangent
+                     turned counter-clockwise.
+        --curvature  Add the signed curvature at every point, `K`, positive where
+                     the path turns counter-clockwise. Points that are not on a
+                     path, such as point infills, get zeros for all of these.
+        --arc-length Add the distance along the path to every point and the
+                     distance along all the paths before it, `S D`. Moves between
+                     subpaths are not counted.
 
 OPTIONS:
     -a, --accuracy <accuracy>    Set tolerance threshold for bezier curve approximation, 
-                                 lower -> higher quality
+                                 lower -> higher quality, in --units or followed by a unit.
                                  [default: 0.0005]
 
-    -d, --distance <distance>    Set Target distance between points, depends on DPI of SVG.
+    -d, --distance <distance>    Set Target distance between points, in --units or followed by
+                                 a unit, such as 0.5mm.
                                  If distance == 0.0 point distance not normalized.
                                  [default: 0.0]
 
@@ -59,6 +655,179 @@ OPTIONS:
                                  number of points.
                                  [default: 0]
 
+        --allocation <allocation>
+                                 How the <points> are split between paths:
+                                   length:  proportional to the length of each path.
+                                   minimum: like length, but at least <min-points> per path.
+                                   equal:   the same number of points for every path.
+                                 [default: length]
+
+        --min-points <min-points>
+                                 Smallest number of points per path with the minimum
+                                 allocation.
+                                 [default: 4]
+
+        --infill <infill>        Also fill the inside of filled paths, respecting their fill-rule:
+                                   grid:        points on a square grid.
+                                   jitter:      a point at a random position in every grid cell.
+                                   poisson:     random points, no closer than the spacing.
+                                   hatch:       parallel lines, written after the outline.
+                                   cross-hatch: two sets of parallel lines at right angles.
+                                   concentric:  loops offset inwards from the outline.
+                                   spiral:      concentric loops joined into a spiral where
+                                                the shape does not split or have holes.
+
+        --infill-spacing <spacing>
+                                 Distance between the points or lines inside filled paths.
+                                 [default: the distance between points on the outline]
+
+        --origin <origin>        Point of the svg written as `0 0`:
+                                   top-left:    the min-x, min-y corner of the viewBox.
+                                   bottom-left: the bottom left corner of the viewBox.
+                                   center:      the center of the viewBox.
+                                   bounds:      the corner of the bounding box of all paths,
+                                                so that all coordinates are positive.
+                                 [default: bottom-left, top-left with --y-down]
+
+        --units <units>          Units of the output coordinates, and of lengths given without
+                                 one, one of mm, in, px or pt. Resolved from the width and
+                                 height of the svg and its viewBox. The lengths of -d, -a,
+                                 --infill-spacing, --translate, --rotate, --fit, --margin,
+                                 --quantize, --rdp, --speed, --dedup and --join can also be
+                                 followed by a unit, such as 0.5mm.
+                                 [default: the user units of the svg]
+
+        --dpi <dpi>              Pixels per inch, for units in the svg and px.
+                                 [default: 96]
+
+        --precision <decimals>   Write every value with this many decimals, at most 12.
+                                 [default: shortest exact representation]
+
+        --quantize <step>        Snap the output coordinates to multiples of <step>, such as
+                                 motor steps. Sets --precision to the decimals of <step>.
+
+        --transform <transform>  Transform the whole svg, using the svg transform syntax, such as
+                                 "matrix(a b c d e f)" or "rotate(90) translate(0 -100)".
+
+        --rotate <degrees>       Rotate the whole svg around the svg origin, clockwise as the
+                                 drawing is seen. Also <degrees,cx,cy> to rotate around (cx, cy),
+                                 in svg user units unless they have a unit.
+
+        --translate <x,y>        Move the whole svg, in svg user units unless they have a unit.
+
+                                 --transform, --rotate and --translate apply in the order given,
+                                 after the transforms inside the svg. --origin and --fit can
+                                 bring the result back into view.
+
+        --fit <x0,y0,x1,y1>      Scale and move the output into this box, keeping the aspect
+                                 ratio and centering it.
+
+        --margin <margin>        Space left inside each side of the --fit box.
+                                 [default: 0]
+
+        --scale <scale>          Scale the output around the origin, after --fit. Either one
+                                 factor or two, <sx,sy>.
+
+        --rdp <tolerance>        Simplify every subpath after sampling with Ramer-Douglas-Peucker,
+                                 keeping the points further than <tolerance> from the result.
+
+        --visvalingam <area>     Simplify every subpath after sampling with Visvalingam-Whyatt,
+                                 dropping points that make a triangle smaller than <area>
+                                 with their neighbours.
+
+        --visvalingam-points <count>
+                                 Like --visvalingam, dropping the smallest triangles until
+                                 <count> points are left in every subpath.
+
+        --hatch-angle <degrees>  Angle of the hatch lines, counter-clockwise from the x axis.
+                                 [default: 45]
+
+        --speed <speed>          Add the time at which every point is drawn at this speed,
+                                 `T`, the distance along all paths divided by the speed.
+
+        --select <id,...>        Only convert the paths with one of these ids, or inside a
+                                 group with one of them. Can be given more than once.
+
+        --exclude <id,...>       Leave out the paths with one of these ids, or inside a group
+                                 with one of them, even when selected.
+
+        --layer <label>          Only convert the paths in the Inkscape layer with this label,
+                                 as well as those selected. Can be given more than once.
+
+        --unpainted <rule>       What to do with paths that have neither fill nor stroke, such
+                                 as motion guides:
+                                   skip:    leave them out.
+                                   include: convert them like the others.
+                                   only:    convert only them, leaving out the painted paths.
+                                 [default: skip]
+
+        --dedup <tolerance>      Drop paths that run within <tolerance> of an earlier path all
+                                 along, in either direction, and the parts of straight segments
+                                 that lie on earlier ones. Reports on stderr what was removed,
+                                 if anything.
+                                 Filled paths are only changed without --infill.
+
+        --join <tolerance>       Join paths into longer ones where their ends are closer than
+                                 <tolerance>, drawing them backwards where needed, so they are
+                                 sampled as one. Only paths made of a single open subpath are
+                                 joined, and filled paths only without --infill.
+
+        --order <order>          Reorder the paths to shorten the pen-up travel between them,
+                                 starting from the output origin, and report the travel
+                                 before and after on stderr. Open paths may be drawn
+                                 backwards, and single closed loops may start at any vertex.
+                                   greedy:  always draw the closest path next.
+                                   two-opt: greedy, then improved with 2-opt, reversing
+                                            runs of up to 50 paths. Up to 100 passes
+                                            of 50 checks per path, slow on big files.
+                                 [default: document order]
+
+        --by-color <paint>       Group the paths by the color of their stroke or fill, for
+                                 multi-pen plots, ordering each group on its own. Every group
+                                 goes to its own file, <output> with the color added to the
+                                 name, such as out_ff0000.pts, or after a `# pen <n> <color>`
+                                 line when writing to stdout or with --pen-markers.
+
+        --split <part>           Write every part of the svg to a file of its own, rather than
+                                 to <output>:
+                                   path:  every path.
+                                   group: every outermost group with an id, such as Inkscape
+                                          layers. Paths outside of one are parts of their own.
+
+        --split-template <template>
+                                 Names of the --split files, in the directory of <output>.
+                                 {{stem}} is the name of <output>, or of <input>, without its
+                                 extension, {{id}} the id of the part, or path or group when it
+                                 has none, and {{index}} its position, counting from 0.
+                                 [default: {{stem}}_{{id}}_{{index}}.pts]
+
+        --orientation <orientation>
+                                 Direction of closed subpaths, as the drawing is seen:
+                                   cw:        all clockwise.
+                                   ccw:       all counter-clockwise.
+                                   cw-outer:  clockwise, holes inside other subpaths of the
+                                              same path counter-clockwise.
+                                   ccw-outer: counter-clockwise, holes clockwise.
+
+        --start <start>          Vertex closed subpaths start from, after --order:
+                                   leftmost: the leftmost, the topmost of those.
+                                   topmost:  the topmost, the leftmost of those.
+                                   nearest:  the closest to the end of the subpath before.
+                                   <x,y>:    the closest to this point, in output coordinates.
+
+        --seed <seed>            Seed for random sampling and the random placement of points.
+                                 [default: 0]
+
+    -s, --sampling <sampling>    Strategy used to place points when a distance is set:
+                                   distance:   points are evenly spaced along the whole path.
+                                   segments:   every segment is split into equal parts,
+                                               keeping the original endpoints.
+                                   random:     points at uniformly random positions along
+                                               the total length of the paths.
+                                   blue-noise: random points kept apart from each other,
+                                               spaced close to the distance on average.
+                                 [default: distance]
+
 ARGS:
     <input>     Input SVG file, stdin if not present
     <output>    Output file, stdout if not present"#
@@ -67,7 +836,6 @@ ARGS:
 
 fn print_basic_usage() {
     println!(
-        "{}",
         r#"
 USAGE:
     svg2pts [OPTIONS] [ <input> [<output>] ]
@@ -78,8 +846,14 @@ For more information try --help
 }
 
 fn parse_args() -> Ret<Opt> {
-    let mut opts = Opt::default();
+    let mut opts = Opt {
+        min_points: 4,
+        hatch_angle: 45.0,
+        split_template: "{stem}_{id}_{index}.pts".to_string(),
+        ..Opt::default()
+    };
     let mut args = std::env::args().skip(1);
+    let mut quantize_precision = None;
 
     while let Some(arg) = args.next() {
         if arg.starts_with('-') {
@@ -91,15 +865,14 @@ fn parse_args() -> Ret<Opt> {
                     format!("Missing argument after: {}", arg)
                 })?; 
 
-                let dist = d.parse::<f64>().map_err(|err| {
-                    format!("{err}: Invalid value '{}' <f64>", arg)
-                })?;
+                let (dist, unit) = parse_length(&arg, &d)?;
 
                 if dist < 0.0 {
                     return Err(format!("{} is out of range, distance >= 0", arg).into());
                 }
 
                 opts.distance = dist;
+                opts.distance_unit = unit;
             } else if arg == "-p" || arg == "--points" {
                 let p = args.next().ok_or_else(|| {
                     format!("Missing argument after: {}", arg)
@@ -116,120 +889,875 @@ fn parse_args() -> Ret<Opt> {
                     format!("Missing argument after: {}", arg)
                 })?;
 
-                let acc = a.parse::<f64>().map_err(|err| {
-                    format!("{err}: Invalid value '{}' <f64>", arg)
-                })?;
+                let (acc, unit) = parse_length(&arg, &a)?;
 
                 if acc <= 0.0 {
                     return Err(format!("{} is out of range, accuracy >= 0", arg).into());
                 }
-                opts.accuracy = Some(acc);
-            } else {
-                print_basic_usage();
-                return Err(format!("unknown flag {}", arg).into());
+                opts.accuracy = Some(acc);
+                opts.accuracy_unit = unit;
+            } else if arg == "--allocation" {
+                let a = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                opts.allocation = a.parse::<Allocation>()?;
+            } else if arg == "--min-points" {
+                let m = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                opts.min_points = m.parse::<u64>().map_err(|err| {
+                    format!("{err}: Invalid value '{}' <u64>", arg)
+                })?;
+            } else if arg == "--infill" {
+                let i = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                opts.infill = Some(i.parse::<Infill>()?);
+            } else if arg == "--infill-spacing" {
+                let s = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                let (spacing, unit) = parse_length(&arg, &s)?;
+
+                if spacing <= 0.0 {
+                    return Err(format!("{} is out of range, spacing > 0", arg).into());
+                }
+                opts.infill_spacing = Some(spacing);
+                opts.infill_spacing_unit = unit;
+            } else if arg == "--rdp" || arg == "--visvalingam" {
+                let t = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                // Areas have no unit, only the --rdp tolerance is a length.
+                let (value, unit) = if arg == "--rdp" {
+                    parse_length(&arg, &t)?
+                } else {
+                    let value = t.parse::<f64>().map_err(|err| {
+                        format!("{err}: Invalid value '{}' <f64>", arg)
+                    })?;
+                    (value, None)
+                };
+
+                if value < 0.0 {
+                    return Err(format!("{} is out of range, value >= 0", arg).into());
+                }
+
+                opts.simplify = Some(if arg == "--rdp" {
+                    Simplify::Rdp(value)
+                } else {
+                    Simplify::Area(value)
+                });
+                opts.rdp_unit = unit;
+            } else if arg == "--visvalingam-points" {
+                let c = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                let count = c.parse::<usize>().map_err(|err| {
+                    format!("{err}: Invalid value '{}' <usize>", arg)
+                })?;
+
+                opts.simplify = Some(Simplify::Count(count));
+            } else if arg == "--hatch-angle" {
+                let a = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                opts.hatch_angle = a.parse::<f64>().map_err(|err| {
+                    format!("{err}: Invalid value '{}' <f64>", arg)
+                })?;
+            } else if arg == "--no-outline" {
+                opts.no_outline = true;
+            } else if arg == "--stroke-outline" {
+                opts.stroke_outline = true;
+            } else if arg == "--dashes" {
+                opts.dashes = true;
+            } else if arg == "-b" || arg == "--blank-lines" {
+                opts.blank_lines = true;
+            } else if arg == "--precision" {
+                let p = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                let precision = p.parse::<usize>().map_err(|err| {
+                    format!("{err}: Invalid value '{}' <usize>", arg)
+                })?;
+
+                if precision > MAX_PRECISION {
+                    return Err(format!("{} is out of range, precision <= {}", arg, MAX_PRECISION).into());
+                }
+
+                opts.precision = Some(precision);
+            } else if arg == "--quantize" {
+                let q = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                let (step, unit) = parse_length(&arg, &q)?;
+
+                if step <= 0.0 {
+                    return Err(format!("{} is out of range, step > 0", arg).into());
+                }
+
+                opts.quantize = Some(step);
+                opts.quantize_unit = unit;
+                // A step with a unit is converted, its decimals say nothing
+                // about those of the output.
+                quantize_precision = None;
+                if unit.is_none() && !q.contains(['e', 'E']) {
+                    let decimals = q.split_once('.').map_or(0, |(_, fraction)| fraction.len());
+                    quantize_precision = Some(decimals.min(MAX_PRECISION));
+                }
+            } else if arg == "--skip-repeats" {
+                opts.skip_repeats = true;
+            } else if arg == "--units" {
+                let u = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                opts.units = Some(u.parse::<Unit>()?);
+            } else if arg == "--dpi" {
+                let d = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                let dpi = d.parse::<f64>().map_err(|err| {
+                    format!("{err}: Invalid value '{}' <f64>", arg)
+                })?;
+
+                if dpi <= 0.0 {
+                    return Err(format!("{} is out of range, dpi > 0", arg).into());
+                }
+
+                opts.dpi = Some(dpi);
+            } else if arg == "--transform" || arg == "--rotate" || arg == "--translate" {
+                let t = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                let transform = if arg == "--transform" {
+                    let ts = t.parse::<svgtypes::Transform>().map_err(|err| {
+                        format!("{err}: Invalid value '{}' <transform>", arg)
+                    })?;
+                    TransformArg::Matrix(Transform::new(ts.a, ts.b, ts.c, ts.d, ts.e, ts.f))
+                } else if arg == "--rotate" {
+                    // The angle is in degrees, only the center is a length.
+                    let r = parse_lengths(&arg, &t, &[1, 3])?;
+                    if r[0].1.is_some() {
+                        return Err(format!("{} takes the angle in degrees, without a unit", arg).into());
+                    }
+                    TransformArg::Rotate(r[0].0, (r.len() == 3).then(|| [r[1], r[2]]))
+                } else {
+                    let d = parse_lengths(&arg, &t, &[2])?;
+                    TransformArg::Translate([d[0], d[1]])
+                };
+                opts.transforms.push(transform);
+            } else if arg == "--fit" {
+                let b = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                let b = parse_lengths(&arg, &b, &[4])?;
+                opts.fit = Some([b[0], b[1], b[2], b[3]]);
+            } else if arg == "--margin" {
+                let m = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                (opts.margin, opts.margin_unit) = parse_length(&arg, &m)?;
+            } else if arg == "--scale" {
+                let s = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                let s = parse_list(&arg, &s, &[1, 2])?;
+                opts.scale = Some(Pt::new(s[0], s[s.len() - 1]));
+            } else if arg == "--stretch" {
+                opts.stretch = true;
+            } else if arg == "--fit-view-box" {
+                opts.fit_view_box = true;
+            } else if arg == "--scale-first" {
+                opts.scale_first = true;
+            } else if arg == "--y-down" {
+                opts.y_down = true;
+            } else if arg == "--origin" {
+                let o = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                opts.origin = Some(o.parse::<Origin>()?);
+            } else if arg == "--tangents" {
+                opts.columns.tangent = true;
+            } else if arg == "--normals" {
+                opts.columns.normal = true;
+            } else if arg == "--curvature" {
+                opts.columns.curvature = true;
+            } else if arg == "--arc-length" {
+                opts.columns.arc_length = true;
+            } else if arg == "--speed" {
+                let s = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                let (speed, unit) = parse_length(&arg, &s)?;
+
+                if speed <= 0.0 {
+                    return Err(format!("{} is out of range, speed > 0", arg).into());
+                }
+
+                opts.columns.speed = Some(speed);
+                opts.speed_unit = unit;
+            } else if arg == "--seed" {
+                let s = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                opts.seed = s.parse::<u64>().map_err(|err| {
+                    format!("{err}: Invalid value '{}' <u64>", arg)
+                })?;
+            } else if arg == "--select" || arg == "--exclude" {
+                let ids = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                let ids = ids.split(',').map(|id| id.trim().to_string()).filter(|id| !id.is_empty());
+                if arg == "--select" {
+                    opts.selection.select.extend(ids);
+                } else {
+                    opts.selection.exclude.extend(ids);
+                }
+            } else if arg == "--layer" {
+                let label = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                opts.layers.push(label);
+            } else if arg == "--unpainted" {
+                let u = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                opts.visibility.unpainted = u.parse::<Unpainted>()?;
+            } else if arg == "--skip-invisible" {
+                opts.visibility.skip_invisible = true;
+            } else if arg == "--report-skipped" {
+                opts.visibility.report = true;
+            } else if arg == "--dedup" {
+                let d = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                let (tolerance, unit) = parse_length(&arg, &d)?;
+
+                if tolerance < 0.0 {
+                    return Err(format!("{} is out of range, tolerance >= 0", arg).into());
+                }
+
+                opts.dedup = Some(tolerance);
+                opts.dedup_unit = unit;
+            } else if arg == "--join" {
+                let j = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                let (tolerance, unit) = parse_length(&arg, &j)?;
+
+                if tolerance < 0.0 {
+                    return Err(format!("{} is out of range, tolerance >= 0", arg).into());
+                }
+
+                opts.join = Some(tolerance);
+                opts.join_unit = unit;
+            } else if arg == "--by-color" {
+                let c = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                opts.pen_color = Some(c.parse::<PenColor>()?);
+            } else if arg == "--split" {
+                let s = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                opts.split = Some(s.parse::<Split>()?);
+            } else if arg == "--split-template" {
+                opts.split_template = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+            } else if arg == "--pen-markers" {
+                opts.pen_markers = true;
+            } else if arg == "--orientation" {
+                let o = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                opts.orientation = Some(o.parse::<loops::Orientation>()?);
+            } else if arg == "--start" {
+                let s = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                opts.start = Some(s.parse::<loops::Start>()?);
+            } else if arg == "--order" {
+                let o = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                opts.order = Some(o.parse::<order::Ordering>()?);
+            } else if arg == "--seamless" {
+                opts.seamless = true;
+            } else if arg == "-s" || arg == "--sampling" {
+                let s = args.next().ok_or_else(|| {
+                    format!("Missing argument after: {}", arg)
+                })?;
+
+                opts.sampling = s.parse::<Sampling>()?;
+            } else {
+                print_basic_usage();
+                return Err(format!("unknown flag {}", arg).into());
+            }
+        } else if opts.input.is_none() {
+            opts.input = Some(arg);
+        } else if opts.output.is_none() {
+            opts.output = Some(arg)
+        } else {
+            return Err(format!("unexpected extra argument {}", arg).into());
+        }
+    }
+
+    if opts.split.is_some() && opts.pen_color.is_some() {
+        return Err("--split and --by-color can't be used together".into());
+    }
+    if opts.split.is_some() && opts.output.is_none() && opts.input.is_none() {
+        return Err("--split needs an <input> or <output> file to name the files after".into());
+    }
+    opts.precision = opts.precision.or(quantize_precision);
+    Ok(opts)
+}
+
+/// Parses the comma separated lengths `value` of the option `arg`, of one
+/// of the lengths in `lens`.
+fn parse_lengths(arg: &str, value: &str, lens: &[usize]) -> Ret<Vec<Length>> {
+    let list = value.split(',')
+        .map(|x| parse_length(arg, x.trim()))
+        .collect::<Ret<Vec<Length>>>()?;
+    if !lens.contains(&list.len()) {
+        return Err(format!("{} expects {:?} comma separated values", arg, lens).into());
+    }
+    Ok(list)
+}
+
+/// Parses the comma separated numbers `value` of the option `arg`, of
+/// one of the lengths in `lens`.
+fn parse_list(arg: &str, value: &str, lens: &[usize]) -> Ret<Vec<f64>> {
+    let list = value.split(',')
+        .map(|x| x.trim().parse::<f64>())
+        .collect::<Result<Vec<f64>, _>>()
+        .map_err(|err| format!("{err}: Invalid value '{}' <f64,...>", arg))?;
+    if !lens.contains(&list.len()) {
+        return Err(format!("{} expects {:?} comma separated values", arg, lens).into());
+    }
+    Ok(list)
+}
+
+
+struct PathWriter {
+    out: PointBufWriter,
+    sampling: Sampling,
+    seamless: bool,    // Evenly space closed subpaths all the way around
+    closing: bool,     // Writing the segment that closes a seamless loop
+    blank_lines: bool, // Separate subpaths with an empty line
+    started: bool,     // A subpath has been written
+    flat: Vec<Pt>,     // Scratch polyline for segment sampling
+    params: Vec<Param>, // Where on the path each point of `flat` is
+    curves: Vec<CubicBezierSegment<f64>>, // Segments of the subpath, lines too
+    seg: usize,        // Index in `curves` of the next segment
+    piece: (Param, Param), // Where on the path the line given to line_to is
+    dist: f64,         // Distance along the path up to `prev`
+    doc_dist: f64,     // Distance along the paths written before
+    tangent: Pt,       // Last tangent written, kept for degenerate curves
+    curvature: f64,    // Last curvature written, likewise
+    columns: Columns,  // Extra values written for every point
+    simplify: Option<Simplify>, // Simplify every subpath once written
+    buffering: bool,   // Rows of the subpath are held back in `rows`
+    rows: Vec<f64>,    // Held back values, `X Y` and the columns per point
+    keep: Vec<bool>,   // Scratch for simplification
+    start: Pt,         // Start of the curve
+    at: Pt,            // Last point written
+    prev: Pt,          // Previous point submited to writer
+    accuracy: f64,     // Tolerance for beizer curve approx. 
+    target_dist: f64,  // If 0.0 don't normalize distance
+    frame: Frame,      // Svg to output coordinates
+}
+
+impl PathWriter {
+    fn new(out: PointBufWriter, target_dist: f64, accuracy: f64, frame: Frame, sampling: Sampling, seamless: bool) -> PathWriter {
+        PathWriter {
+            target_dist,
+            start: Pt::default(),
+            at: Pt::default(),
+            prev: Pt::default(),
+            accuracy,
+            sampling,
+            seamless,
+            closing: false,
+            blank_lines: false,
+            started: false,
+            flat: Vec::new(),
+            params: Vec::new(),
+            curves: Vec::new(),
+            seg: 0,
+            piece: (Param::default(), Param::default()),
+            dist: 0.0,
+            doc_dist: 0.0,
+            tangent: Pt::new(1.0, 0.0),
+            curvature: 0.0,
+            columns: Columns::default(),
+            simplify: None,
+            buffering: false,
+            rows: Vec::new(),
+            keep: Vec::new(),
+            frame,
+            out,
+        }
+    }
+
+    /// Writes a `# text` line, starting a new group of subpaths.
+    fn write_comment(&mut self, text: &str) -> io::Result<()> {
+        self.out.write_line(&format!("# {}", text))?;
+        self.started = false;
+        Ok(())
+    }
+
+    /// Continues in `out` as if nothing had been written yet.
+    fn restart(&mut self, out: Box<dyn Write>) -> io::Result<()> {
+        self.out.replace_output(out)?;
+        self.started = false;
+        self.doc_dist = 0.0;
+        Ok(())
+    }
+
+    fn write_pt(&mut self, pt: Pt) -> io::Result<()> {
+        let n = self.columns.len();
+        if n > 0 {
+            return self.write_row(self.frame.apply(pt), &[0.0; 8][..n]);
+        }
+        self.write_row(self.frame.apply(pt), &[])
+    }
+
+    fn write_row(&mut self, pt: Pt, extra: &[f64]) -> io::Result<()> {
+        if self.buffering {
+            self.rows.extend_from_slice(&[pt.x, pt.y]);
+            self.rows.extend_from_slice(extra);
+            return Ok(());
+        }
+        self.out.write_row(pt.x, pt.y, extra)
+    }
+
+    /// Writes the rows held back for the subpath, simplified.
+    fn flush_rows(&mut self, closed: bool) -> io::Result<()> {
+        self.buffering = false;
+        let Some(method) = self.simplify else {
+            return Ok(());
+        };
+        let stride = 2 + self.columns.len();
+        let pts: Vec<Pt> = self.rows.chunks(stride).map(|row| Pt::new(row[0], row[1])).collect();
+        simplify::simplify(&pts, closed, method, &mut self.keep);
+        for (row, &keep) in self.rows.chunks(stride).zip(&self.keep) {
+            if keep {
+                self.out.write_row(row[0], row[1], &row[2..])?;
+            }
+        }
+        self.rows.clear();
+        Ok(())
+    }
+
+    /// Writes `pt`, which lies at `param` on the path, along with the
+    /// tangent, normal, curvature and distances there when they are wanted.
+    fn write_on_path(&mut self, pt: Pt, param: Param) -> io::Result<()> {
+        if self.columns.len() == 0 || param.curve >= self.curves.len() {
+            return self.write_pt(pt);
+        }
+        if self.columns.tangent || self.columns.normal || self.columns.curvature {
+            let bez = &self.curves[param.curve];
+            // The derivative vanishes where a control point sits on an end
+            // point, the direction just inside the curve is the limit there.
+            let mut t = param.t;
+            if bez.derivative(t).square_length() < 1e-24 {
+                t = t.clamp(1e-6, 1.0 - 1e-6);
+            }
+            // In output coordinates, which may be flipped and scaled.
+            let d = self.frame.vector(bez.derivative(t));
+            if d.square_length() >= 1e-24 {
+                self.tangent = d.normalize() + Pt::zero(); // Not -0.0
+                let dd = (bez.ctrl2 - bez.ctrl1 * 2.0 + bez.from.to_vector()) * (6.0 * (1.0 - t))
+                    + (bez.to - bez.ctrl2 * 2.0 + bez.ctrl1.to_vector()) * (6.0 * t);
+                self.curvature = d.cross(self.frame.vector(dd)) / d.length().powi(3) + 0.0;
+            }
+        }
+
+        let tangent = self.tangent;
+        let mut extra = [0.0; 8];
+        let mut n = 0;
+        if self.columns.tangent {
+            extra[n..n + 2].copy_from_slice(&[tangent.x, tangent.y]);
+            n += 2;
+        }
+        if self.columns.normal {
+            extra[n..n + 2].copy_from_slice(&[-tangent.y, tangent.x]);
+            n += 2;
+        }
+        if self.columns.curvature {
+            extra[n] = self.curvature;
+            n += 1;
+        }
+        if self.columns.arc_length {
+            extra[n..n + 2].copy_from_slice(&[param.dist, self.doc_dist + param.dist]);
+            n += 2;
+        }
+        if let Some(speed) = self.columns.speed {
+            extra[n] = (self.doc_dist + param.dist) / speed;
+            n += 1;
+        }
+        self.write_row(self.frame.apply(pt), &extra[..n])
+    }
+
+    /// Collects the segments of `subpath` into `curves`, lines as straight
+    /// cubics so every segment has the same parametrization.
+    fn set_curves(&mut self, subpath: &[PathSegment]) {
+        use PathSegment::*;
+        let line = |from: Pt, to: Pt| CubicBezierSegment {
+            from: from.to_point(),
+            ctrl1: from.lerp(to, 1.0 / 3.0).to_point(),
+            ctrl2: from.lerp(to, 2.0 / 3.0).to_point(),
+            to: to.to_point(),
+        };
+        self.curves.clear();
+        self.seg = 0;
+        let mut start = Pt::default();
+        let mut last = Pt::default();
+        for seg in subpath {
+            match *seg {
+                MoveTo { x, y } => {
+                    start = Pt::new(x, y);
+                    last = start;
+                }
+                LineTo { x, y } => {
+                    self.curves.push(line(last, Pt::new(x, y)));
+                    last = Pt::new(x, y);
+                }
+                ClosePath => {
+                    self.curves.push(line(last, start));
+                    last = start;
+                }
+                CurveTo { x1, y1, x2, y2, x, y } => {
+                    self.curves.push(CubicBezierSegment {
+                        from: last.to_point(),
+                        ctrl1: (x1, y1).into(),
+                        ctrl2: (x2, y2).into(),
+                        to: (x, y).into(),
+                    });
+                    last = Pt::new(x, y);
+                }
+            }
+        }
+    }
+
+    fn write_path(&mut self, path: &PathData) -> io::Result<()> {
+        self.doc_dist += self.dist;
+        self.dist = 0.0;
+        for subpath in path.subpaths() {
+            self.set_curves(&subpath);
+            self.buffering = self.simplify.is_some();
+            let closed = matches!(subpath.last(), Some(PathSegment::ClosePath));
+            if closed && self.seamless {
+                self.write_loop(&subpath)?;
+            } else {
+                for seg in subpath.iter() {
+                    self.write_segment(*seg)?;
+                }
+            }
+            self.flush_rows(closed)?;
+        }
+        Ok(())
+    }
+
+    /// Writes points lying on `path`, each with the index of its subpath
+    /// and how far along the flattened subpath it is, in path order.
+    fn write_along(&mut self, path: &PathData, pts: &[(Pt, usize, f64)]) -> io::Result<()> {
+        self.doc_dist += self.dist;
+        self.dist = 0.0;
+        let mut pts = pts.iter().peekable();
+        for (index, subpath) in path.subpaths().enumerate() {
+            self.set_curves(&subpath);
+            self.flat.clear();
+            self.params.clear();
+            for seg in subpath.iter() {
+                if let PathSegment::MoveTo { x, y } = *seg {
+                    self.start = (x, y).into();
+                }
+                self.push_flat(*seg);
+            }
+            let (flat, params) = (std::mem::take(&mut self.flat), std::mem::take(&mut self.params));
+            let mut line = 1; // Index in `flat` of the end of the line the point is on
+            let mut walked = 0.0; // Length of the lines before `line`
+            while let Some(&(pt, _, along)) = pts.next_if(|&&(_, subpath, _)| subpath == index) {
+                let param = if flat.len() < 2 {
+                    params[0]
+                } else {
+                    let mut line_len = (flat[line] - flat[line - 1]).length();
+                    while line + 1 < flat.len() && walked + line_len < along {
+                        walked += line_len;
+                        line += 1;
+                        line_len = (flat[line] - flat[line - 1]).length();
+                    }
+                    let f = if line_len > 0.0 { (along - walked) / line_len } else { 1.0 };
+                    piece_param(&params, line, f.clamp(0.0, 1.0))
+                };
+                self.write_on_path(pt, param)?;
+            }
+            self.flat = flat;
+            self.params = params;
+        }
+        Ok(())
+    }
+
+    /// Writes a closed subpath so that the points are evenly spaced all
+    /// the way around and the start point is not repeated at the end.
+    fn write_loop(&mut self, subpath: &[PathSegment]) -> io::Result<()> {
+        if self.sampling == Sampling::Distance && self.target_dist > 0.0 {
+            // Divide the whole perimeter at once, the distance is adjusted
+            // to fit a whole number of intervals.
+            self.flat.clear();
+            self.params.clear();
+            for seg in subpath {
+                if let PathSegment::MoveTo { x, y } = *seg {
+                    self.move_to((x, y).into())?;
+                }
+                self.push_flat(*seg);
+            }
+            self.closing = true;
+            self.divide_flat()?;
+            self.closing = false;
+            self.at = self.start;
+            return Ok(());
+        }
+
+        // The segment that returns to the start, ClosePath is a no-op when
+        // the segment before it already ends at the start.
+        let mut closing = subpath.len() - 1;
+        if let (Some(&PathSegment::MoveTo { x: sx, y: sy }),
+                Some(&PathSegment::LineTo { x, y } | &PathSegment::CurveTo { x, y, .. }))
+            = (subpath.first(), subpath.get(closing.wrapping_sub(1)))
+        {
+            if is_near(Pt::new(sx, sy), Pt::new(x, y)) {
+                closing -= 1;
+            }
+        }
+        for (i, seg) in subpath.iter().enumerate() {
+            self.closing = i >= closing;
+            self.write_segment(*seg)?;
+        }
+        self.closing = false;
+        Ok(())
+    }
+
+    /// Starts a new subpath at `pt`.
+    fn move_to(&mut self, pt: Pt) -> io::Result<()> {
+        self.start = pt;
+        self.at = pt;
+        self.prev = pt;
+        if self.blank_lines && self.started {
+            self.out.write_break()?;
+        }
+        self.started = true;
+        self.write_on_path(pt, Param { curve: 0, t: 0.0, dist: self.dist })
+    }
+
+    fn write_segment(&mut self, seg: PathSegment) -> io::Result<()> {
+        use PathSegment::*;
+        let divide = self.sampling == Sampling::Segments && self.target_dist > 0.0;
+        match seg {
+            MoveTo { x, y } => self.move_to((x, y).into()),
+            // Where the points are on the path is only needed for the columns.
+            _ if divide || self.columns.len() > 0 => self.write_flat(seg),
+            LineTo { x, y } => self.line_to((x, y).into()),
+            ClosePath => self.line_to(self.start),
+            CurveTo { x1, y1, x2, y2, x, y } => {
+                let bez = CubicBezierSegment {
+                    from: (self.prev.x, self.prev.y).into(),
+                    ctrl1: (x1, y1).into(),
+                    ctrl2: (x2, y2).into(),
+                    to: (x, y).into(),
+                };
+                for pt in bez.flattened(self.accuracy) {
+                    self.line_to(pt.to_vector())?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes a segment through `flat`, keeping track of where on the path
+    /// every point is.
+    fn write_flat(&mut self, seg: PathSegment) -> io::Result<()> {
+        self.flat.clear();
+        self.params.clear();
+        self.push_flat(seg);
+        if self.sampling == Sampling::Segments && self.target_dist > 0.0 {
+            self.divide_flat()
+        } else {
+            let flat = std::mem::take(&mut self.flat);
+            for (i, &pt) in flat.iter().enumerate().skip(1) {
+                self.piece = (piece_param(&self.params, i, 0.0), self.params[i]);
+                self.line_to(pt)?;
+            }
+            self.flat = flat;
+            Ok(())
+        }
+    }
+
+    /// Appends the flattened segment to `flat`, starting with the
+    /// previous point when `flat` is empty.
+    fn push_flat(&mut self, seg: PathSegment) {
+        use PathSegment::*;
+        let curve = self.seg;
+        if !matches!(seg, MoveTo { .. }) {
+            self.seg += 1;
+        }
+        let start = Param { curve, t: 0.0, dist: self.dist };
+        let from = match self.flat.last() {
+            Some(&pt) => pt,
+            None => {
+                self.flat.push(self.prev);
+                self.params.push(start);
+                self.prev
+            }
+        };
+        let find_t = self.columns.len() > 0;
+        let find_dist = self.columns.needs_dist();
+        let (flat, params) = (&mut self.flat, &mut self.params);
+        let mut push = |pt: Pt, t: f64| {
+            let mut dist = params[params.len() - 1].dist;
+            if find_dist {
+                dist += (pt - flat[flat.len() - 1]).length();
+            }
+            flat.push(pt);
+            params.push(Param { curve, t, dist });
+        };
+        match seg {
+            MoveTo { x, y } => {
+                self.prev = (x, y).into();
+                self.flat.clear();
+                self.params.clear();
+                self.flat.push(self.prev);
+                self.params.push(start);
+            }
+            LineTo { x, y } => push((x, y).into(), 1.0),
+            ClosePath => push(self.start, 1.0),
+            CurveTo { x1, y1, x2, y2, x, y } => {
+                let bez = CubicBezierSegment {
+                    from: (from.x, from.y).into(),
+                    ctrl1: (x1, y1).into(),
+                    ctrl2: (x2, y2).into(),
+                    to: (x, y).into(),
+                };
+                let mut t = 0.0;
+                for pt in bez.flattened(self.accuracy) {
+                    if find_t {
+                        t = curve_param(&bez, pt.to_vector(), t);
+                    }
+                    push(pt.to_vector(), t);
+                }
             }
-        } else if opts.input.is_none() {
-            opts.input = Some(arg);
-        } else if opts.output.is_none() {
-            opts.output = Some(arg)
-        } else {
-            return Err(format!("unexpected extra argument {}", arg).into());
         }
+        self.dist = self.params[self.params.len() - 1].dist;
     }
 
-    Ok(opts)
-}
+    /// Writes the polyline in `flat` split into a whole number of equal
+    /// length parts, the first point is assumed to be already written.
+    fn divide_flat(&mut self) -> io::Result<()> {
+        let flat = std::mem::take(&mut self.flat);
+        let end = *flat.last().unwrap();
+        self.prev = end;
+        let length: f64 = flat.windows(2).map(|w| (w[1] - w[0]).length()).sum();
+        if length <= f64::EPSILON { // Nothing new to write
+            self.flat = flat;
+            return Ok(());
+        }
 
+        let parts = (length / self.target_dist).round().max(1.0) as usize;
+        let step = length / parts as f64;
+        let params = std::mem::take(&mut self.params);
+        let mut line = 1; // Index in `flat` of the end of the current line
+        let mut walked = 0.0; // Length of the lines before `line`
+        let mut line_len = (flat[1] - flat[0]).length();
+        for i in 1..parts {
+            let target = step * i as f64;
+            while walked + line_len < target && line + 1 < flat.len() {
+                walked += line_len;
+                line += 1;
+                line_len = (flat[line] - flat[line - 1]).length();
+            }
+            let t = ((target - walked) / line_len).clamp(0.0, 1.0);
+            self.write_on_path(flat[line - 1].lerp(flat[line], t), piece_param(&params, line, t))?;
+        }
+        self.write_end(end, params[params.len() - 1])?;
+        self.params = params;
 
-struct PathWriter {
-    out: PointBufWriter,
-    var_distance: bool,
-    start: Pt,         // Start of the curve
-    at: Pt,            // Last point written
-    prev: Pt,          // Previous point submited to writer
-    accuracy: f64,     // Tolerance for beizer curve approx. 
-    target_dist: f64,  // If 0.0 don't normalize distance
-    height: f64,       // For flipping svg
-}
+        self.flat = flat;
+        Ok(())
+    }
 
-impl PathWriter {
-    fn new(out: PointBufWriter, target_dist: f64, accuracy: f64, height: f64, var_distance: bool) -> PathWriter {
-        PathWriter {
-            target_dist,
-            start: Pt::default(),
-            at: Pt::default(),
-            prev: Pt::default(),
-            accuracy,
-            var_distance,
-            height,
-            out,
+    /// Writes the final point of a segment, which is skipped when it
+    /// closes a seamless loop.
+    fn write_end(&mut self, pt: Pt, param: Param) -> io::Result<()> {
+        if self.closing && is_near(pt, self.start) {
+            return Ok(());
         }
+        self.write_on_path(pt, param)
     }
 
-    fn write_pt(&mut self, pt: Pt) -> io::Result<()> {
-        self.out.write(pt.x, self.height - pt.y)
+    /// Where on `curves` the point `f` of the way along the current
+    /// line_to line is.
+    fn line_param(&self, f: f64) -> Param {
+        let (start, end) = self.piece;
+        Param {
+            curve: end.curve,
+            t: start.t + (end.t - start.t) * f,
+            dist: start.dist + (end.dist - start.dist) * f,
+        }
     }
 
-    fn write_path(&mut self, path: impl Iterator<Item = PathSegment>) -> io::Result<()> {
-        use PathSegment::*;
-        for seg in path {
-            match seg {
-                MoveTo { x, y } => {
-                    let pt = (x,y).into();
-                    self.start = pt;
-                    self.at = pt;
-                    self.prev = pt;
-                    self.write_pt(pt)?;
-                }
-                LineTo { x, y } => {
-                    self.line_to((x, y).into())?;
-                }
-                ClosePath => {
-                    self.line_to(self.start)?;
-                }
-                CurveTo { x1, y1, x2, y2, x, y } => {
-                    let bez = CubicBezierSegment {
-                        from: (self.prev.x, self.prev.y).into(),
-                        ctrl1: (x1, y1).into(),
-                        ctrl2: (x2, y2).into(),
-                        to: (x, y).into(),
-                    };
-                    for pt in bez.flattened(self.accuracy) {
-                        self.line_to(pt.to_vector())?;
-                    }
-                }
-            }
+    /// Writes `pt`, which is `f` of the way along the current line_to line.
+    fn write_on_line(&mut self, pt: Pt, f: f64) -> io::Result<()> {
+        if self.columns.len() == 0 {
+            return self.write_pt(pt);
         }
-        Ok(())
+        self.write_on_path(pt, self.line_param(f))
     }
+
     /// Segments Line into distance lengthed segments
     fn line_to(&mut self, line_end: Pt) -> io::Result<()> {
         let line_start = self.prev;
         self.prev = line_end;
         if self.target_dist == 0.0 { //Don't normalize distance
-            return self.write_pt(line_end)
-        }
-        if self.var_distance { //different method
-            let line_dist = (self.at - line_end).length();
-            let pts = (line_dist/self.target_dist).round();
-            if pts >= 2.0 {
-                let t_delta = 1.0 / pts;
-                for i in 1..(1.0/t_delta) as i64 {
-                    self.write_pt(self.at.lerp(line_end, (i as f64) * t_delta))?;
-                }
-            }
-            self.write_pt(line_end)?;
-            self.at = line_end;
-            return Ok(());
+            return self.write_end(line_end, self.piece.1)
         }
 
+        let along; // How far along the line self.at is
         {   // Find point on line (self.last, line_end) such that is
             // target_dist away from self.current
 
             let w = line_end - self.at;
             let v = line_start - line_end;
             let c = w.square_length() - self.target_dist*self.target_dist;
-            if c < 0.0 { // line_end is two close 
+            if c < -2.0 * DIST_SLACK * self.target_dist*self.target_dist { // line_end is two close 
                 return Ok(());
             }
 
@@ -242,27 +1770,79 @@ impl PathWriter {
 
             //Move onto line
             self.at = line_end.lerp(line_start, t_min);
-            self.write_pt(self.at)?;
+            self.write_on_line(self.at, 1.0 - t_min)?;
+            along = 1.0 - t_min;
         }
 
         // Calculate additional points on lines 
         let line_dist = (self.at - line_end).length();
-        if line_dist < self.target_dist { //already to close to end of line.
+        if line_dist < self.target_dist * (1.0 - DIST_SLACK) { //already to close to end of line.
             return Ok(()); 
         }
 
         let t_delta = self.target_dist / line_dist;
 
         let line_start = self.at; 
-        for i in 1..=(1.0/t_delta) as i64 {
+        for i in 1..=(1.0/t_delta + DIST_SLACK) as i64 {
             self.at = line_start.lerp(line_end, (i as f64) * t_delta);
-            self.write_pt(self.at)?;
+            self.write_on_line(self.at, along + (1.0 - along) * (i as f64) * t_delta)?;
         }
 
         Ok(())
     }
 }
 
+/// Relative slack on the distance between points, so lines a whole number
+/// of steps long keep their last point despite rounding.
+const DIST_SLACK: f64 = 1e-9;
+
+/// Where a point is on the path being written.
+#[derive(Debug, Clone, Copy, Default)]
+struct Param {
+    curve: usize, // Index in `PathWriter::curves`
+    t: f64,       // Parameter on the curve
+    dist: f64,    // Distance along the flattened path
+}
+
+/// Where on the path the point `f` of the way along the line of a
+/// flattened polyline ending at `params[end]` is. The line belongs to the
+/// curve of its end, its start may still be the end of the curve before.
+fn piece_param(params: &[Param], end: usize, f: f64) -> Param {
+    let (start, end) = (params[end - 1], params[end]);
+    let t0 = if start.curve == end.curve { start.t } else { 0.0 };
+    Param {
+        curve: end.curve,
+        t: t0 + (end.t - t0) * f,
+        dist: start.dist + (end.dist - start.dist) * f,
+    }
+}
+
+/// The parameter of `pt`, a point of `bez` a little past the parameter
+/// `t`, found with Newton's method as flattening doesn't give it.
+fn curve_param(bez: &CubicBezierSegment<f64>, pt: Pt, t: f64) -> f64 {
+    if pt == bez.to.to_vector() {
+        return 1.0;
+    }
+    let mut s = t;
+    for _ in 0..16 {
+        let mut d = bez.derivative(s);
+        if d.square_length() < 1e-24 {
+            d = bez.derivative(s.clamp(1e-6, 1.0 - 1e-6));
+        }
+        let step = (pt - bez.sample(s).to_vector()).dot(d) / d.square_length().max(1e-24);
+        s = (s + step).clamp(t, 1.0);
+        if step.abs() < 1e-12 {
+            break;
+        }
+    }
+    s
+}
+
+/// Whether two points are the same, allowing for rounding errors.
+fn is_near(a: Pt, b: Pt) -> bool {
+    (a - b).square_length() < 1e-18
+}
+
 /// the deafult stdout is line-buffered causing considerable
 /// overhead, on unix this is trival to work around.
 #[cfg(target_family = "unix")]
@@ -316,16 +1896,232 @@ fn path_distance(
     dist
 }
 
+/// Splits a budget of `points` between paths of the given `lengths`,
+/// returning the target distance for each path.
+fn allocate_points(allocation: Allocation, min_points: u64, lengths: &[f64], points: u64) -> Vec<f64> {
+    let points = points as f64;
+    let total: f64 = lengths.iter().sum();
+    let count = lengths.iter().filter(|&&len| len > 0.0).count() as f64;
+    let global = total / points;
+
+    let per_path = match allocation {
+        Allocation::Length => return vec![global; lengths.len()],
+        Allocation::Equal => points / count,
+        Allocation::Minimum => {
+            let min = min_points as f64;
+            if min * count >= points {
+                points / count
+            } else {
+                // Paths too short for `min` points at the shared distance are
+                // clamped to `min`, the rest share what is left. Clamping only
+                // grows as the shared distance grows, so this settles quickly.
+                let mut distance = global;
+                loop {
+                    let (clamped, free_len) = lengths.iter()
+                        .filter(|&&len| len > 0.0)
+                        .fold((0.0, 0.0), |(clamped, free_len), &len| {
+                            if len / distance < min {
+                                (clamped + 1.0, free_len)
+                            } else {
+                                (clamped, free_len + len)
+                            }
+                        });
+                    let next = free_len / (points - min * clamped);
+                    if next <= distance || free_len == 0.0 {
+                        break;
+                    }
+                    distance = next;
+                }
+                return lengths.iter().map(|&len| {
+                    if len > 0.0 { distance.min(len / min) } else { global }
+                }).collect();
+            }
+        }
+    };
+    lengths.iter().map(|&len| if len > 0.0 { len / per_path } else { global }).collect()
+}
+
+/// Corners of the bounding box of all `paths`, none when they are empty.
+fn bounds(paths: &[PathData]) -> Option<(Pt, Pt)> {
+    let mut min = Pt::new(f64::INFINITY, f64::INFINITY);
+    let mut max = Pt::new(f64::NEG_INFINITY, f64::NEG_INFINITY);
+    // Not PathData::bbox, which has none for dots and flat paths.
+    for path in paths {
+        let mut prev = Pt::zero();
+        for seg in path.iter() {
+            let (lo, hi) = match *seg {
+                PathSegment::MoveTo { x, y } | PathSegment::LineTo { x, y } => {
+                    prev = Pt::new(x, y);
+                    (prev, prev)
+                }
+                PathSegment::CurveTo { x1, y1, x2, y2, x, y } => {
+                    let bez = CubicBezierSegment {
+                        from: prev.to_point(),
+                        ctrl1: (x1, y1).into(),
+                        ctrl2: (x2, y2).into(),
+                        to: (x, y).into(),
+                    };
+                    let bbox = bez.bounding_box();
+                    prev = Pt::new(x, y);
+                    (bbox.min.to_vector(), bbox.max.to_vector())
+                }
+                PathSegment::ClosePath => continue,
+            };
+            min = min.min(lo);
+            max = max.max(hi);
+        }
+    }
+    (min.x <= max.x).then_some((min, max))
+}
+
+/// Flattens every subpath into a polyline, along with whether the
+/// subpath is closed. Closed polylines end with their first point.
+fn flatten(path: &PathData, accuracy: f64) -> Vec<(Vec<Pt>, bool)> {
+    use PathSegment::*;
+    let mut lines = Vec::new();
+    for subpath in path.subpaths() {
+        let mut line: Vec<Pt> = Vec::new();
+        let mut closed = false;
+        for seg in subpath.iter() {
+            match *seg {
+                MoveTo { x, y } | LineTo { x, y } => line.push(Pt::new(x, y)),
+                ClosePath => {
+                    closed = true;
+                    line.push(line[0]);
+                }
+                CurveTo { x1, y1, x2, y2, x, y } => {
+                    let from = line.last().copied().unwrap_or_default();
+                    let bez = CubicBezierSegment {
+                        from: (from.x, from.y).into(),
+                        ctrl1: (x1, y1).into(),
+                        ctrl2: (x2, y2).into(),
+                        to: (x, y).into(),
+                    };
+                    line.extend(bez.flattened(accuracy).map(|pt| pt.to_vector()));
+                }
+            }
+        }
+        lines.push((line, closed));
+    }
+    lines
+}
+
 use usvg::PathData;
 use usvg::Transform;
+use std::borrow::Cow;
 use std::rc::Rc;
 
-fn extract_paths(svg: &Tree) -> Vec<(Rc<PathData>, Transform)> {
+/// A visible path of the svg, along with the styles used to generate
+/// its points.
+struct SvgPath {
+    node: usvg::Node, // Element the path comes from
+    data: Rc<PathData>,
+    transform: Transform,
+    fill: Option<usvg::Fill>,
+    stroke: Option<usvg::Stroke>,
+}
+
+/// What is done with paths that have neither fill nor stroke.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Unpainted {
+    #[default]
+    Skip,
+    Include,
+    /// Skip the painted paths instead, such as to get only motion guides.
+    Only,
+}
+
+impl std::str::FromStr for Unpainted {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Unpainted, String> {
+        match s {
+            "skip" => Ok(Unpainted::Skip),
+            "include" => Ok(Unpainted::Include),
+            "only" => Ok(Unpainted::Only),
+            _ => Err(format!("unknown rule '{}', expected skip, include or only", s)),
+        }
+    }
+}
+
+/// Which paths of the svg are converted.
+#[derive(Debug, Clone, Copy, Default)]
+struct Visibility {
+    unpainted: Unpainted,
+    /// Skip paths that are hidden, fully transparent, or inside a group
+    /// with no opacity.
+    skip_invisible: bool,
+    /// Print every path skipped on stderr, and why.
+    report: bool,
+}
+
+impl Visibility {
+    /// Why the path `node` is skipped, none when it is converted.
+    fn skipped(&self, node: &usvg::Node, path: &usvg::Path) -> Option<&'static str> {
+        let painted = path.fill.is_some() || path.stroke.is_some();
+        match self.unpainted {
+            Unpainted::Skip if !painted => return Some("no fill or stroke"),
+            Unpainted::Only if painted => return Some("painted"),
+            _ => {}
+        }
+        if self.skip_invisible {
+            if path.visibility != usvg::Visibility::Visible {
+                return Some("visibility hidden");
+            }
+            let opacity: f64 = node.ancestors().map(|node| match *node.borrow() {
+                NodeKind::Group(ref group) => group.opacity.value(),
+                _ => 1.0,
+            }).product();
+            if opacity == 0.0 {
+                return Some("opacity 0");
+            }
+            let fill = path.fill.as_ref().map_or(0.0, |fill| fill.opacity.value());
+            let stroke = path.stroke.as_ref().map_or(0.0, |stroke| stroke.opacity.value());
+            if painted && fill == 0.0 && stroke == 0.0 {
+                return Some("transparent paint");
+            }
+        }
+        None
+    }
+}
+
+/// Collects the paths of `svg` in `selection` that `visibility` allows,
+/// with `transform` applied after their own.
+fn extract_paths(
+    svg: &Tree,
+    transform: Transform,
+    selection: &select::Selection,
+    visibility: Visibility,
+) -> Vec<SvgPath> {
     let mut paths = Vec::default();
+    let mut index = 0; // Of the path elements
     for node in svg.root().descendants() {
         if let NodeKind::Path(ref path) = *node.borrow() {
-            if path.fill.is_some() || path.stroke.is_some() {
-                paths.push((path.data.clone(), node.transform()));
+            index += 1;
+            let skipped = if selection.accepts(&node) {
+                visibility.skipped(&node, path)
+            } else {
+                Some("not selected")
+            };
+            if let Some(reason) = skipped {
+                if visibility.report {
+                    if path.id.is_empty() {
+                        eprintln!("skipped path {}: {}", index, reason);
+                    } else {
+                        eprintln!("skipped path {} '{}': {}", index, path.id, reason);
+                    }
+                }
+            } else {
+                paths.push(SvgPath {
+                    node: node.clone(),
+                    data: path.data.clone(),
+                    transform: {
+                        let mut ts = transform;
+                        ts.append(&node.abs_transform());
+                        ts
+                    },
+                    fill: path.fill.clone(),
+                    stroke: path.stroke.clone(),
+                });
             }
         }
     }
@@ -340,38 +2136,155 @@ struct PointBufWriter {
     buf: Box<[u8; BUFFER_SIZE]>,
     out: Box<dyn Write>,
     pos: usize,
+    precision: Option<usize>, // Fixed number of decimals
+    quantize: Option<f64>,    // Grid step the coordinates snap to
+    skip_repeats: bool,       // Drop points equal to the one before
+    last: Option<(f64, f64)>, // Last point written since a break, as written
 }
 
+/// Largest `precision` supported, fixed values then fit in `VALUE_SIZE`.
+const MAX_PRECISION: usize = 12;
+/// Room needed for a value and the separator after it, ryu needs 24 bytes.
+const VALUE_SIZE: usize = 36;
+
 impl PointBufWriter {
     fn new(writer: Box<dyn Write>) -> PointBufWriter {
         PointBufWriter{
             buf: Box::new([0; BUFFER_SIZE]),
             out: writer,
             pos: 0,
+            precision: None,
+            quantize: None,
+            skip_repeats: false,
+            last: None,
         }
     }
 
-    fn write(&mut self, x: f64, y: f64) -> io::Result<()> {
-        use ryu::raw::format64;
-        if (self.pos + 51) >= BUFFER_SIZE { //ENSURE atleast 51 bytes free.
+    /// Writes a point followed by `extra` values on the same line.
+    fn write_row(&mut self, x: f64, y: f64, extra: &[f64]) -> io::Result<()> {
+        let (x, y) = match self.quantize {
+            Some(step) => ((x / step).round() * step + 0.0, (y / step).round() * step + 0.0),
+            None => (x, y),
+        };
+        if self.skip_repeats {
+            // Compare the points as written.
+            let key = match self.precision {
+                Some(precision) => {
+                    let scale = 10f64.powi(precision as i32);
+                    ((x * scale).round(), (y * scale).round())
+                }
+                None => (x, y),
+            };
+            if self.last == Some(key) {
+                return Ok(());
+            }
+            self.last = Some(key);
+        }
+
+        //ENSURE enough bytes free for every value.
+        if (self.pos + VALUE_SIZE * (2 + extra.len())) >= BUFFER_SIZE {
             self.out.write_all(&self.buf[..self.pos])?;
             self.pos = 0;
         }
-        let buf = self.buf.as_mut_ptr() as *mut u8;
-        let mut pos = self.pos as isize;
+        self.put(x);
+        self.buf[self.pos] = b' ';
+        self.pos += 1;
+        self.put(y);
+        for &value in extra {
+            self.buf[self.pos] = b' ';
+            self.pos += 1;
+            self.put(value);
+        }
+        self.buf[self.pos] = b'\n';
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// Formats `value` at the end of the buffer, which must have
+    /// `VALUE_SIZE` bytes free.
+    fn put(&mut self, value: f64) {
+        use ryu::raw::format64;
+        if let Some(precision) = self.precision {
+            if let Some(len) = format_fixed(&mut self.buf[self.pos..], value, precision) {
+                self.pos += len;
+                return;
+            }
+        }
         unsafe {
-            // Format64 need 24 bytes each to writes to be safe
-            // the two char writes use 2 more bytes
-            // the total written is 50 bytes at maximum
-            // The above check ensures there always enough room.
-            pos += format64(x, buf.offset(pos)) as isize;
-            *buf.offset(pos) = b' ';
-            pos += 1;
-            pos += format64(y, buf.offset(pos)) as isize;
-            *buf.offset(pos) = b'\n';
-            pos += 1;
-        }
-        self.pos = pos as usize;
+            // Format64 need 24 bytes to writes to be safe,
+            // the callers ensure there always enough room.
+            self.pos += format64(value, self.buf.as_mut_ptr().add(self.pos));
+        }
+    }
+}
+
+/// Writes `value` rounded to `precision` decimals into `buf`, returning the
+/// length written. None when the value is too large to be written exactly.
+fn format_fixed(buf: &mut [u8], value: f64, precision: usize) -> Option<usize> {
+    let scaled = (value * 10f64.powi(precision as i32)).round();
+    if scaled.is_nan() || scaled.abs() >= 1e15 {
+        return None;
+    }
+    let mut digits = scaled.abs() as u64;
+    let mut pos = 0;
+    if scaled < 0.0 {
+        buf[0] = b'-';
+        pos += 1;
+    }
+    // Digits are written backwards, with at least one before the point.
+    let len = (digits.checked_ilog10().unwrap_or(0) as usize + 1).max(precision + 1);
+    let end = pos + len + (precision > 0) as usize;
+    let mut at = end;
+    for i in 0..len {
+        if i == precision && precision > 0 {
+            at -= 1;
+            buf[at] = b'.';
+        }
+        at -= 1;
+        buf[at] = b'0' + (digits % 10) as u8;
+        digits /= 10;
+    }
+    Some(end)
+}
+
+impl PointBufWriter {
+    /// Writes `text` on a line of its own.
+    fn write_line(&mut self, text: &str) -> io::Result<()> {
+        if self.pos + text.len() + 1 >= BUFFER_SIZE {
+            self.out.write_all(&self.buf[..self.pos])?;
+            self.pos = 0;
+        }
+        if text.len() + 1 >= BUFFER_SIZE {
+            self.out.write_all(text.as_bytes())?;
+        } else {
+            self.buf[self.pos..self.pos + text.len()].copy_from_slice(text.as_bytes());
+            self.pos += text.len();
+        }
+        self.buf[self.pos] = b'\n';
+        self.pos += 1;
+        self.last = None;
+        Ok(())
+    }
+
+    /// Writes what is buffered, and continues writing to `out`.
+    fn replace_output(&mut self, out: Box<dyn Write>) -> io::Result<()> {
+        self.out.write_all(&self.buf[..self.pos])?;
+        self.out.flush()?;
+        self.pos = 0;
+        self.out = out;
+        self.last = None;
+        Ok(())
+    }
+
+    /// Writes an empty line, marking a break between subpaths.
+    fn write_break(&mut self) -> io::Result<()> {
+        if self.pos + 1 >= BUFFER_SIZE {
+            self.out.write_all(&self.buf[..self.pos])?;
+            self.pos = 0;
+        }
+        self.buf[self.pos] = b'\n';
+        self.pos += 1;
+        self.last = None;
         Ok(())
     }
 }
@@ -386,8 +2299,29 @@ impl Drop for PointBufWriter {
 }
 
 
+/// Whether every color or part gets its own file instead of the output.
+fn file_per_group(opt: &Opt) -> bool {
+    opt.split.is_some() || opt.pen_color.is_some() && opt.output.is_some() && !opt.pen_markers
+}
+
+/// The writer of the output, writing nowhere when every group gets a
+/// file of its own.
+fn open_output(opt: &Opt) -> Ret<PointBufWriter> {
+    let mut pt_writer = if file_per_group(opt) {
+        PointBufWriter::new(Box::new(io::sink()))
+    } else if let Some(ref filename) = opt.output {
+        PointBufWriter::new(Box::new(File::create(filename)
+                                     .map_err(|err| format!("{err}: Failed to open output"))?))
+    } else {
+        PointBufWriter::new(Box::new(raw_stdout()))
+    };
+    pt_writer.precision = opt.precision;
+    pt_writer.skip_repeats = opt.skip_repeats;
+    Ok(pt_writer)
+}
+
 fn run() -> Ret<()> {
-    let opt = parse_args()?;
+    let mut opt = parse_args()?;
 
     let mut svg_buf = Vec::default();
 
@@ -401,39 +2335,306 @@ fn run() -> Ret<()> {
             .map_err(|err| format!("{err}: Failed to reading from stdin"))?;
     }
 
-    let pt_writer = if let Some(ref filename) = opt.output {
-        PointBufWriter::new(Box::new(File::create(filename)
-                                     .map_err(|err| format!("{err}: Failed to open output"))?))
-    } else {
-        PointBufWriter::new(Box::new(raw_stdout()))
-    };
+    let mut pt_writer = open_output(&opt)?;
 
-    let tree = Tree::from_data(&svg_buf, &usvg::Options::default().to_ref())
+    let mut options = usvg::Options::default();
+    if let Some(dpi) = opt.dpi {
+        options.dpi = dpi;
+    }
+    // Groups are only kept when their ids are needed.
+    options.keep_named_groups = !opt.selection.is_empty() || !opt.layers.is_empty()
+        || opt.split == Some(Split::Group);
+    let tree = Tree::from_data(&svg_buf, &options.to_ref())
         .map_err(|err| format!("{err}: Unable to parse svg"))?;
+    opt.selection.check(&tree)?;
+    if !opt.layers.is_empty() {
+        let layers = select::layer_ids(&svg_buf, &opt.layers)?;
+        opt.selection.select.extend(layers);
+    }
 
-    let paths = extract_paths(&tree);
-
-    let height = tree.svg_node().view_box.rect.height();
-
-    let distance = if opt.points > 0 {
-        let path_distance:f64 = paths.iter().map(|(path, transform)| path_distance(
-            0.05, TransformedPath::new(path, *transform)
-        )).sum();
-        path_distance / (opt.points as f64) 
-    } else {
-        opt.distance
+    // Pixels per user unit, from the size of the svg and its viewBox.
+    let svg = tree.svg_node();
+    let px_per_unit = (svg.size.width() / svg.view_box.rect.width())
+        .min(svg.size.height() / svg.view_box.rect.height());
+    let units_in_px = opt.units.map_or(px_per_unit, |units| units.in_px(options.dpi));
+    let convert = |value: f64, unit: Option<Unit>| {
+        unit.map_or(value, |unit| value * unit.in_px(options.dpi) / units_in_px)
     };
+    opt.distance = convert(opt.distance, opt.distance_unit);
+    opt.accuracy = opt.accuracy.map(|accuracy| convert(accuracy, opt.accuracy_unit));
+    opt.join = opt.join.map(|tolerance| convert(tolerance, opt.join_unit));
+    opt.dedup = opt.dedup.map(|tolerance| convert(tolerance, opt.dedup_unit));
+    opt.infill_spacing = opt.infill_spacing.map(|spacing| convert(spacing, opt.infill_spacing_unit));
+    if let Some(Simplify::Rdp(ref mut tolerance)) = opt.simplify {
+        *tolerance = convert(*tolerance, opt.rdp_unit);
+    }
+    opt.quantize = opt.quantize.map(|step| convert(step, opt.quantize_unit));
+    pt_writer.quantize = opt.quantize;
+    let fit = opt.fit.map(|b| {
+        let (a, b) = (Pt::new(convert(b[0].0, b[0].1), convert(b[1].0, b[1].1)),
+                      Pt::new(convert(b[2].0, b[2].1), convert(b[3].0, b[3].1)));
+        (a.min(b), a.max(b))
+    });
+    opt.margin = convert(opt.margin, opt.margin_unit);
+    opt.columns.speed = opt.columns.speed.map(|speed| convert(speed, opt.speed_unit));
+    // The whole svg is scaled into the output units, so distances are
+    // measured in them as well.
+    let unit_scale = px_per_unit / units_in_px;
+    // The transforms given apply in svg user units, before that.
+    let user = |(value, unit): Length| unit.map_or(value, |unit| value * unit.in_px(options.dpi) / px_per_unit);
+    let mut transform = Transform::default();
+    for arg in &opt.transforms {
+        let mut ts = Transform::default();
+        match *arg {
+            TransformArg::Matrix(matrix) => ts = matrix,
+            TransformArg::Rotate(angle, None) => ts.rotate(angle),
+            TransformArg::Rotate(angle, Some([cx, cy])) => ts.rotate_at(angle, user(cx), user(cy)),
+            TransformArg::Translate([x, y]) => ts.translate(user(x), user(y)),
+        }
+        transform.prepend(&ts);
+    }
+    transform.prepend(&Transform::new_scale(unit_scale, unit_scale));
 
-    let accuracy = opt.accuracy.unwrap_or(if distance == 0.0 {
+    let accuracy = |distance: f64| opt.accuracy.unwrap_or(if distance == 0.0 {
         0.05
     } else {
         distance / 25.0
     });
-    let mut writer = PathWriter::new(pt_writer, distance, accuracy, height, opt.var_distance);
 
-    for (path, transform) in &paths {
-        writer.write_path(TransformedPath::new(path, *transform))
-            .map_err(|err| format!("{err}: failed to write points"))?;
+    let svg_paths = extract_paths(&tree, transform, &opt.selection, opt.visibility);
+    // `scale` is how much the paths are scaled after their transform.
+    let build_paths = |scale: f64| -> Vec<PathData> { svg_paths.iter().map(|svg_path| {
+        // Strokes are handled before the transform, which scales them too.
+        let (sx, sy) = svg_path.transform.get_scale();
+        let local_accuracy = accuracy(opt.distance) / (sx.max(sy) * scale);
+        let mut path = PathData::clone(&svg_path.data);
+        if let Some(ref stroke) = svg_path.stroke {
+            if let (true, Some(dasharray)) = (opt.dashes, &stroke.dasharray) {
+                path = stroke::dashes(&path, dasharray, stroke.dashoffset.into(), local_accuracy);
+            }
+            if opt.stroke_outline {
+                path = stroke::outline(&path, stroke, local_accuracy);
+            }
+        }
+        path.transform(svg_path.transform);
+        path
+    }).collect() };
+    let mut paths = build_paths(1.0);
+
+    let view_box = tree.svg_node().view_box.rect.transform(&Transform::new_scale(unit_scale, unit_scale))
+        .ok_or("empty viewBox")?;
+    let origin = match opt.origin.unwrap_or(if opt.y_down { Origin::TopLeft } else { Origin::BottomLeft }) {
+        Origin::TopLeft => Pt::new(view_box.x(), view_box.y()),
+        Origin::BottomLeft => Pt::new(view_box.x(), view_box.bottom()),
+        Origin::Center => Pt::new(view_box.x() + view_box.width() / 2.0,
+                                  view_box.y() + view_box.height() / 2.0),
+        Origin::Bounds => {
+            let (min, max) = bounds(&paths).unwrap_or_default();
+            Pt::new(min.x, if opt.y_down { min.y } else { max.y })
+        }
+    };
+    let mut frame = Frame { origin, y_up: !opt.y_down, scale: Pt::new(1.0, 1.0), offset: Pt::zero() };
+
+    if let Some((target_min, target_max)) = fit {
+        let source = if opt.fit_view_box {
+            Some((Pt::new(view_box.x(), view_box.y()), Pt::new(view_box.right(), view_box.bottom())))
+        } else {
+            bounds(&paths)
+        };
+        if let Some((a, b)) = source {
+            let (a, b) = (frame.apply(a), frame.apply(b));
+            let (min, max) = (a.min(b), a.max(b));
+            let margin = Pt::new(opt.margin, opt.margin);
+            let (target_min, target_max) = (target_min + margin, target_max - margin);
+            let (size, target) = (max - min, target_max - target_min);
+            if target.x < 0.0 || target.y < 0.0 {
+                return Err("--margin is larger than the --fit box".into());
+            }
+            // Flat sources are only moved along their flat axis.
+            let fit = |size: f64, target: f64| if size > 0.0 { target / size } else { f64::INFINITY };
+            let (fx, fy) = (fit(size.x, target.x), fit(size.y, target.y));
+            frame.scale = if opt.stretch {
+                Pt::new(fx, fy)
+            } else {
+                Pt::new(fx.min(fy), fx.min(fy))
+            };
+            for scale in [&mut frame.scale.x, &mut frame.scale.y] {
+                if *scale == f64::INFINITY {
+                    *scale = 1.0;
+                }
+            }
+            let center = (min + max) / 2.0;
+            frame.offset = (target_min + target_max) / 2.0 - Pt::new(center.x * frame.scale.x, center.y * frame.scale.y);
+        }
+    }
+    if let Some(scale) = opt.scale {
+        frame.scale = Pt::new(frame.scale.x * scale.x, frame.scale.y * scale.y);
+        frame.offset = Pt::new(frame.offset.x * scale.x, frame.offset.y * scale.y);
+    }
+
+    // Scaling the paths instead, the frame keeps only moving the origin and
+    // flipping.
+    let mut pre_scale = Transform::default();
+    if opt.scale_first {
+        let Frame { scale, offset, origin, .. } = frame;
+        pre_scale = Transform::new_scale(scale.x, scale.y);
+        let flip = if frame.y_up { -1.0 } else { 1.0 };
+        frame.origin = Pt::new(origin.x * scale.x - offset.x, origin.y * scale.y - flip * offset.y);
+        frame.scale = Pt::new(1.0, 1.0);
+        frame.offset = Pt::zero();
+        if pre_scale != Transform::default() {
+            // Rebuilt so strokes are flattened as accurately as the rest.
+            paths = build_paths(scale.x.abs().max(scale.y.abs()));
+            for path in &mut paths {
+                path.transform(pre_scale);
+            }
+        }
+    }
+
+    let parts = group_keys(&opt, &svg_paths);
+    let keys: Vec<&Option<String>> = parts.iter().map(|(key, _)| key).collect();
+
+    // Paths are only edited where that leaves their infill alone, and only
+    // merged with paths of their group. Joined paths are drawn with the
+    // styles of the first path in them.
+    let mut sources: Vec<usize> = (0..paths.len()).collect();
+    let editable = |i: usize| opt.infill.is_none() || svg_paths[i].fill.is_none();
+    if let Some(tolerance) = opt.dedup {
+        let mut removed = dedup::Removed::default();
+        (paths, sources) = per_group(paths, sources, &keys, |paths, sources| {
+            let (left, kept, group_removed) = dedup::dedup(paths, |i| editable(sources[i]), tolerance, accuracy(opt.distance));
+            removed.paths += group_removed.paths;
+            removed.segments += group_removed.segments;
+            (left, kept)
+        });
+        if removed.paths > 0 || removed.segments > 0 {
+            eprintln!("removed {} duplicate paths, trimmed {} overlapping segments", removed.paths, removed.segments);
+        }
+    }
+    if let Some(tolerance) = opt.join {
+        (paths, sources) = per_group(paths, sources, &keys, |paths, sources| {
+            join::join(paths, |i| editable(sources[i]), tolerance)
+        });
+    }
+
+    let lengths: Vec<f64> = if opt.points > 0 || opt.sampling.is_random() {
+        paths.iter().map(|path| path_distance(0.05, path.iter().copied())).collect()
+    } else {
+        Vec::new()
+    };
+    let distances = if opt.points > 0 {
+        allocate_points(opt.allocation, opt.min_points, &lengths, opt.points)
+    } else {
+        vec![opt.distance; paths.len()]
+    };
+
+    if opt.infill.is_some() && opt.infill_spacing.is_none() && opt.distance == 0.0 && opt.points == 0 {
+        return Err("--infill requires --infill-spacing, --distance or --points".into());
+    }
+    let mut rng = rng::Rng::new(opt.seed);
+    let mut infill_pts = Vec::new();
+    let mut scattered = Vec::new(); // Random points along the path and where they are on it
+
+    // Random sampling draws every point along the combined length first,
+    // so the number of points on a path follows its length.
+    let mut scatter = None;
+    let mut counts = Vec::new();
+    if opt.sampling.is_random() {
+        if opt.points == 0 && opt.distance == 0.0 {
+            return Err("random sampling requires --distance or --points".into());
+        }
+        let weights: Vec<f64> = lengths.iter().zip(&distances)
+            .map(|(&length, &distance)| if distance > 0.0 { length / distance } else { 0.0 })
+            .collect();
+        let total = if opt.points > 0 {
+            opt.points as usize
+        } else {
+            weights.iter().sum::<f64>().round() as usize
+        };
+        counts = scatter::split_points(&weights, total, &mut rng);
+        let cell = distances.iter().copied().filter(|&d| d > 0.0).fold(f64::INFINITY, f64::min);
+        scatter = Some(scatter::Scatter::new(opt.sampling == Sampling::BlueNoise, cell));
+    }
+    let mut hatch = PathData::new(); // Infill made of paths
+
+
+    let (stops, headers) = plan_groups(&opt, &paths, &sources, &parts, &frame);
+
+    let mut writer = PathWriter::new(pt_writer, 0.0, 0.0, frame, opt.sampling, opt.seamless);
+    writer.blank_lines = opt.blank_lines;
+    writer.columns = opt.columns;
+    writer.simplify = opt.simplify;
+
+    let mut pen = frame.origin; // End of the last subpath written
+    let mut headers = headers.into_iter().peekable();
+    for (n, (i, entry)) in stops.into_iter().enumerate() {
+        if let Some((_, header)) = headers.next_if(|(at, _)| *at == n) {
+            match header {
+                Header::Pen(text) => writer.write_comment(&text),
+                Header::File(name) => {
+                    let file = File::create(&name)
+                        .map_err(|err| format!("{err}: Failed to open output {}", name.display()))?;
+                    writer.restart(Box::new(file))
+                }
+            }.map_err(|err| format!("{err}: failed to write points"))?;
+        }
+        let (path, svg_path, distance) = (&paths[i], &svg_paths[sources[i]], distances[i]);
+        let mut outline = order::apply(path, entry);
+        if opt.orientation.is_some() || opt.start.is_some() {
+            outline = Cow::Owned(loops::arrange(&outline, opt.orientation, opt.start, &mut pen, |pt| frame.apply(pt)));
+        }
+        writer.target_dist = distance;
+        writer.accuracy = accuracy(distance);
+        // Without a spacing, the inside is as dense as the outline.
+        let infill_spacing = opt.infill_spacing.unwrap_or(distance);
+        let infill = opt.infill.zip(svg_path.fill.as_ref()).filter(|_| infill_spacing > 0.0);
+        if opt.no_outline && infill.is_some() && svg_path.stroke.is_none() {
+            // Only the inside is wanted
+        } else if let Some(ref mut scatter) = scatter {
+            scattered.clear();
+            scatter.scatter(&outline, counts[i], distance, accuracy(distance), &mut rng, &mut scattered);
+            writer.write_along(&outline, &scattered)
+                .map_err(|err| format!("{err}: failed to write points"))?;
+        } else {
+            writer.write_path(&outline)
+                .map_err(|err| format!("{err}: failed to write points"))?;
+        }
+
+        if let Some((infill, fill)) = infill {
+            let area = if opt.stroke_outline && svg_path.stroke.is_some() {
+                let mut area = PathData::clone(&svg_path.data);
+                area.transform(svg_path.transform);
+                area.transform(pre_scale);
+                Cow::Owned(area)
+            } else {
+                Cow::Borrowed(path)
+            };
+            let path = &*area;
+            if infill.is_path() {
+                let acc = accuracy(infill_spacing);
+                hatch.clear();
+                match infill {
+                    Infill::Concentric | Infill::Spiral => offset::concentric(
+                        path, fill.rule, acc, infill_spacing, infill == Infill::Spiral, &mut hatch
+                    ),
+                    _ => fill::hatch(path, fill.rule, acc, infill_spacing, opt.hatch_angle, &mut hatch),
+                }
+                if infill == Infill::CrossHatch {
+                    fill::hatch(path, fill.rule, acc, infill_spacing,
+                                opt.hatch_angle + 90.0, &mut hatch);
+                }
+                writer.write_path(&hatch)
+                    .map_err(|err| format!("{err}: failed to write points"))?;
+            } else {
+                let shape = fill::Shape::new(path, fill.rule, accuracy(infill_spacing));
+                infill_pts.clear();
+                fill::sample(&shape, infill, infill_spacing, &mut rng, &mut infill_pts);
+                for &pt in &infill_pts {
+                    writer.write_pt(pt)
+                        .map_err(|err| format!("{err}: failed to write points"))?;
+                }
+            }
+        }
     }
 
     Ok(())
@@ -0,0 +1,375 @@
+//! Concentric and spiral infill, built from inward offsets of a filled
+//! path.
+//!
+//! The outline is intersected with the rows and columns of a grid, a
+//! euclidean distance transform finds the distance from every grid node to
+//! the nearest of those intersections, and the offset loops are the
+//! iso-lines of that field found with marching squares.
+
+use crate::fill::{winding, Shape};
+use crate::Pt;
+use usvg::{FillRule, PathData};
+
+/// Upper bound on the grid size, the grid is coarsened for big shapes.
+const MAX_NODES: f64 = 1_000_000.0;
+/// Squared distance standing in for infinity in the distance transform.
+const FAR: f64 = 1e20;
+const NONE: u32 = u32::MAX;
+
+/// Appends loops offset inwards from the outline of `path` by multiples of
+/// `spacing` to `out`. With `spiral` nested loops are joined into a single
+/// subpath where the shape does not split or have holes.
+pub fn concentric(
+    path: &PathData,
+    rule: FillRule,
+    accuracy: f64,
+    spacing: f64,
+    spiral: bool,
+    out: &mut PathData,
+) {
+    let shape = Shape::new(path, rule, accuracy);
+    if shape.is_empty() || spacing <= 0.0 {
+        return;
+    }
+    let field = Field::new(&shape, spacing);
+
+    let mut levels: Vec<Vec<Vec<Pt>>> = Vec::new();
+    let mut level = spacing;
+    loop {
+        let loops = field.contours(level);
+        if loops.is_empty() {
+            break;
+        }
+        levels.push(loops);
+        level += spacing;
+    }
+
+    if spiral {
+        write_spirals(&levels, out);
+    } else {
+        for ring in levels.iter().flatten() {
+            write_ring(ring, out);
+        }
+    }
+}
+
+fn write_ring(ring: &[Pt], out: &mut PathData) {
+    out.push_move_to(ring[0].x, ring[0].y);
+    for pt in &ring[1..] {
+        out.push_line_to(pt.x, pt.y);
+    }
+    out.push_close_path();
+}
+
+/// Distance to the outline, sampled on a grid covering the shape.
+struct Field {
+    origin: Pt,
+    step: f64,
+    cols: usize,
+    rows: usize,
+    dist: Vec<f64>,
+}
+
+impl Field {
+    fn new(shape: &Shape, spacing: f64) -> Field {
+        let size = shape.max - shape.min;
+        let step = (spacing / 4.0).max((size.x * size.y / MAX_NODES).sqrt());
+        // A row and column of padding keeps the border outside the shape.
+        let origin = shape.min - Pt::new(step, step);
+        let cols = (size.x / step).ceil() as usize + 3;
+        let rows = (size.y / step).ceil() as usize + 3;
+
+        // Exact distances to the outline along every grid row and column,
+        // the transform then finds the nearest of those outline points.
+        let mut inside = vec![false; cols * rows];
+        let mut along_rows = vec![FAR; cols * rows];
+        let mut along_cols = vec![FAR; cols * rows];
+        let mut spans = Vec::new();
+        for row in 0..rows {
+            shape.spans(origin.y + row as f64 * step, &mut spans);
+            let line = row * cols..(row + 1) * cols;
+            nearest_ends(&spans, origin.x / step, step, &mut along_rows[line.clone()]);
+            for &(start, end) in &spans {
+                let first = ((start - origin.x) / step).ceil().max(0.0) as usize;
+                let last = (((end - origin.x) / step).floor() as usize).min(cols - 1);
+                for node in &mut inside[line.start + first..=line.start + last] {
+                    *node = true;
+                }
+            }
+        }
+        let transposed = shape.transposed();
+        let mut column = vec![0.0; rows];
+        for col in 0..cols {
+            transposed.spans(origin.x + col as f64 * step, &mut spans);
+            nearest_ends(&spans, origin.y / step, step, &mut column);
+            for (row, &dist) in column.iter().enumerate() {
+                along_cols[row * cols + col] = dist;
+            }
+        }
+
+        distance_transform(&mut along_rows, cols, rows, true);
+        distance_transform(&mut along_cols, cols, rows, false);
+        let dist = along_rows.iter().zip(&along_cols).zip(&inside)
+            .map(|((&a, &b), &inside)| {
+                let dist = a.min(b).sqrt() * step;
+                if inside { dist } else { -dist }
+            })
+            .collect();
+        Field { origin, step, cols, rows, dist }
+    }
+
+    /// Closed loops where the distance to the outline is `level`.
+    fn contours(&self, level: f64) -> Vec<Vec<Pt>> {
+        // Every crossed grid edge is the start of exactly one directed
+        // segment, with the inside on the same side. Edges are keyed as
+        // `2 * node` for the edge to the right of a node and `2 * node + 1`
+        // for the edge below it.
+        let inside = |node: usize| self.dist[node] >= level;
+        let mut next = vec![NONE; 2 * self.cols * self.rows];
+        for row in 0..self.rows - 1 {
+            for col in 0..self.cols - 1 {
+                let c0 = row * self.cols + col;
+                let corners = [c0, c0 + 1, c0 + 1 + self.cols, c0 + self.cols];
+                let edges = [2 * c0, 2 * (c0 + 1) + 1, 2 * (c0 + self.cols), 2 * c0 + 1];
+                // Crossings in order around the cell, leaving the inside or not.
+                let mut crossings = [(0, false); 4];
+                let mut count = 0;
+                for i in 0..4 {
+                    let (a, b) = (inside(corners[i]), inside(corners[(i + 1) % 4]));
+                    if a != b {
+                        crossings[count] = (edges[i], a);
+                        count += 1;
+                    }
+                }
+                // At a saddle the average decides if the inside corners connect.
+                let connected = count == 4 && corners.iter()
+                    .map(|&c| self.dist[c]).sum::<f64>() / 4.0 >= level;
+                for i in 0..count {
+                    let (edge, leaving) = crossings[i];
+                    if leaving {
+                        let partner = if connected { i + 1 } else { i + count - 1 };
+                        next[edge] = crossings[partner % count].0 as u32;
+                    }
+                }
+            }
+        }
+
+        let mut loops = Vec::new();
+        for start in 0..next.len() {
+            if next[start] == NONE {
+                continue;
+            }
+            let mut ring = Vec::new();
+            let mut edge = start;
+            while next[edge] != NONE {
+                ring.push(self.crossing(edge, level));
+                let following = next[edge] as usize;
+                next[edge] = NONE;
+                edge = following;
+            }
+            if ring.len() > 2 {
+                loops.push(ring);
+            }
+        }
+        loops
+    }
+
+    /// Where the field crosses `level` along the grid edge `edge`.
+    fn crossing(&self, edge: usize, level: f64) -> Pt {
+        let a = edge / 2;
+        let b = if edge % 2 == 0 { a + 1 } else { a + self.cols };
+        let (da, db) = (self.dist[a], self.dist[b]);
+        let t = ((level - da) / (db - da)).clamp(0.0, 1.0);
+        self.node(a).lerp(self.node(b), t)
+    }
+
+    fn node(&self, node: usize) -> Pt {
+        self.origin + Pt::new((node % self.cols) as f64, (node / self.cols) as f64) * self.step
+    }
+}
+
+/// Sets `line[i]` to the squared distance, in grid steps, from the node
+/// at `offset + i` steps to the nearest end of `spans`.
+fn nearest_ends(spans: &[(f64, f64)], offset: f64, step: f64, line: &mut [f64]) {
+    let mut ends = spans.iter().flat_map(|&(start, end)| [start / step, end / step]).peekable();
+    let mut before = f64::NEG_INFINITY;
+    for (i, node) in line.iter_mut().enumerate() {
+        let at = offset + i as f64;
+        while let Some(&end) = ends.peek() {
+            if end > at {
+                break;
+            }
+            before = end;
+            ends.next();
+        }
+        let after = ends.peek().copied().unwrap_or(f64::INFINITY);
+        let dist = (at - before).min(after - at);
+        *node = if dist.is_finite() { dist * dist } else { FAR };
+    }
+}
+
+/// Squared distance transform of Felzenszwalb and Huttenlocher along the
+/// columns of the grid, or along its rows when `by_column` is false.
+fn distance_transform(grid: &mut [f64], cols: usize, rows: usize, by_column: bool) {
+    let len = cols.max(rows);
+    let mut f = vec![0.0; len];
+    let mut d = vec![0.0; len];
+    let mut v = vec![0; len];
+    let mut z = vec![0.0; len + 1];
+    if by_column {
+        for col in 0..cols {
+            for row in 0..rows {
+                f[row] = grid[row * cols + col];
+            }
+            distance_transform_1d(&f[..rows], &mut d, &mut v, &mut z);
+            for row in 0..rows {
+                grid[row * cols + col] = d[row];
+            }
+        }
+    } else {
+        for row in grid.chunks_mut(cols) {
+            f[..cols].copy_from_slice(row);
+            distance_transform_1d(&f[..cols], &mut d, &mut v, &mut z);
+            row.copy_from_slice(&d[..cols]);
+        }
+    }
+}
+
+fn distance_transform_1d(f: &[f64], d: &mut [f64], v: &mut [usize], z: &mut [f64]) {
+    let parabola = |q: usize, p: usize| {
+        ((f[q] + (q * q) as f64) - (f[p] + (p * p) as f64)) / (2.0 * q as f64 - 2.0 * p as f64)
+    };
+    let mut k = 0;
+    v[0] = 0;
+    z[0] = f64::NEG_INFINITY;
+    z[1] = f64::INFINITY;
+    for q in 1..f.len() {
+        let mut s = parabola(q, v[k]);
+        while s <= z[k] {
+            k -= 1;
+            s = parabola(q, v[k]);
+        }
+        k += 1;
+        v[k] = q;
+        z[k] = s;
+        z[k + 1] = f64::INFINITY;
+    }
+    k = 0;
+    for (q, out) in d[..f.len()].iter_mut().enumerate() {
+        while z[k + 1] < q as f64 {
+            k += 1;
+        }
+        let dq = q as f64 - v[k] as f64;
+        *out = dq * dq + f[v[k]];
+    }
+}
+
+/// Joins loops into spirals, a loop continues into the next level when
+/// it holds exactly one loop there and that loop is in no other.
+fn write_spirals(levels: &[Vec<Vec<Pt>>], out: &mut PathData) {
+    // child[level][i] is the only loop of the next level inside loop i.
+    let child: Vec<Vec<Option<usize>>> = levels.iter().enumerate().map(|(level, loops)| {
+        let inner = match levels.get(level + 1) {
+            Some(inner) => inner,
+            None => return vec![None; loops.len()],
+        };
+        let parents: Vec<Vec<usize>> = inner.iter().map(|ring| {
+            (0..loops.len()).filter(|&i| winding(&closed(&loops[i]), ring[0]) != 0).collect()
+        }).collect();
+        (0..loops.len()).map(|i| {
+            let mut children = parents.iter().enumerate()
+                .filter(|(_, parents)| parents.contains(&i));
+            match (children.next(), children.next()) {
+                (Some((j, parents)), None) if parents.len() == 1 => Some(j),
+                _ => None,
+            }
+        }).collect()
+    }).collect();
+
+    let mut used: Vec<Vec<bool>> = levels.iter().map(|loops| vec![false; loops.len()]).collect();
+    for level in 0..levels.len() {
+        for i in 0..levels[level].len() {
+            if used[level][i] {
+                continue;
+            }
+            let mut ring = levels[level][i].clone();
+            let (mut level, mut i) = (level, i);
+            used[level][i] = true;
+            let mut turns = 0;
+            out.push_move_to(ring[0].x, ring[0].y);
+            while let Some(j) = child[level][i] {
+                turns += 1;
+                let inner = start_near(&levels[level + 1][j], ring[0]);
+                write_spiral_turn(&ring, &inner, out);
+                ring = inner;
+                level += 1;
+                i = j;
+                used[level][i] = true;
+            }
+            for pt in &ring[1..] {
+                out.push_line_to(pt.x, pt.y);
+            }
+            if turns == 0 {
+                out.push_close_path();
+            } else {
+                // Closing would return to the outside of the spiral.
+                out.push_line_to(ring[0].x, ring[0].y);
+            }
+        }
+    }
+}
+
+/// The ring with its first point repeated at the end.
+fn closed(ring: &[Pt]) -> Vec<Pt> {
+    let mut closed = ring.to_vec();
+    closed.push(ring[0]);
+    closed
+}
+
+/// Rotates `ring` to start at its point nearest to `pt`.
+fn start_near(ring: &[Pt], pt: Pt) -> Vec<Pt> {
+    let start = (0..ring.len())
+        .min_by(|&a, &b| {
+            (ring[a] - pt).square_length().total_cmp(&(ring[b] - pt).square_length())
+        })
+        .unwrap_or(0);
+    let mut rotated = ring[start..].to_vec();
+    rotated.extend_from_slice(&ring[..start]);
+    rotated
+}
+
+/// Writes one turn going around `outer` while moving onto `inner`, ending
+/// at the start of `inner`.
+fn write_spiral_turn(outer: &[Pt], inner: &[Pt], out: &mut PathData) {
+    let outer = closed(outer);
+    let inner = closed(inner);
+    let outer_len = length(&outer);
+    let inner_len = length(&inner);
+
+    let mut walked = 0.0;
+    let mut inner_walked = 0.0;
+    let mut inner_edge = 0;
+    for edge in outer.windows(2) {
+        let t = walked / outer_len;
+        // Point at the same fraction of the way around `inner`.
+        let target = t * inner_len;
+        let mut edge_len = (inner[inner_edge + 1] - inner[inner_edge]).length();
+        while inner_walked + edge_len < target && inner_edge + 2 < inner.len() {
+            inner_walked += edge_len;
+            inner_edge += 1;
+            edge_len = (inner[inner_edge + 1] - inner[inner_edge]).length();
+        }
+        let s = if edge_len > 0.0 { ((target - inner_walked) / edge_len).clamp(0.0, 1.0) } else { 0.0 };
+        let on_inner = inner[inner_edge].lerp(inner[inner_edge + 1], s);
+        let pt = edge[0].lerp(on_inner, t);
+        if walked > 0.0 {
+            out.push_line_to(pt.x, pt.y);
+        }
+        walked += (edge[1] - edge[0]).length();
+    }
+    out.push_line_to(inner[0].x, inner[0].y);
+}
+
+fn length(line: &[Pt]) -> f64 {
+    line.windows(2).map(|w| (w[1] - w[0]).length()).sum()
+}
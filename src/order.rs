@@ -0,0 +1,283 @@
+//! Ordering of paths to reduce the pen-up travel of plotters.
+//!
+//! Every path is kept whole, its subpaths are drawn in their own order.
+//! Open paths may be drawn backwards, and paths made of a single closed
+//! subpath may start at any of their vertices.
+
+use crate::Pt;
+use std::borrow::Cow;
+use usvg::{PathData, PathSegment};
+
+/// Passes of 2-opt over the stops before giving up on improving further.
+const MAX_PASSES: usize = 100;
+/// Longest run of stops 2-opt reverses, so a pass takes time linear in
+/// the number of paths.
+const MAX_RUN: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ordering {
+    /// Always draw the closest path next.
+    Greedy,
+    /// Greedy, then improved by reversing runs of paths while that
+    /// shortens the travel.
+    TwoOpt,
+}
+
+impl std::str::FromStr for Ordering {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Ordering, String> {
+        match s {
+            "greedy" => Ok(Ordering::Greedy),
+            "two-opt" => Ok(Ordering::TwoOpt),
+            _ => Err(format!("unknown order '{}', expected greedy or two-opt", s)),
+        }
+    }
+}
+
+/// How a path is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entry {
+    Forward,
+    Reversed,
+    /// A closed loop started from this vertex.
+    From(usize),
+}
+
+/// A path to draw, with the points the pen enters and leaves it at.
+#[derive(Debug, Clone, Copy)]
+struct Stop {
+    index: usize,
+    entry: Entry,
+    enter: Pt,
+    leave: Pt,
+}
+
+impl Stop {
+    /// The stop drawn the other way around, loops are kept as they are.
+    fn flipped(self) -> Stop {
+        let entry = match self.entry {
+            Entry::Forward => Entry::Reversed,
+            Entry::Reversed => Entry::Forward,
+            loop_entry => loop_entry,
+        };
+        Stop { entry, enter: self.leave, leave: self.enter, ..self }
+    }
+}
+
+/// Where a path can be entered, in output coordinates.
+struct Ends {
+    start: Pt,
+    end: Pt,
+    /// The vertices of a single closed subpath and their bounding box.
+    vertices: Option<(Vec<Pt>, Pt, Pt)>,
+}
+
+/// The paths in the order to draw them, and the pen-up travel in
+/// document order and in that order.
+pub struct Plan {
+    pub stops: Vec<(usize, Entry)>,
+    pub travel_before: f64,
+    pub travel_after: f64,
+}
+
+/// The order in which to draw `paths` for a pen starting at `pen`,
+/// measuring distances between points mapped through `map`. Empty paths
+/// are left at the end.
+pub fn plan(paths: &[&PathData], ordering: Ordering, pen: Pt, map: impl Fn(Pt) -> Pt) -> Plan {
+    let ends: Vec<Option<Ends>> = paths.iter().map(|path| ends(path, &map)).collect();
+    let document: Vec<Stop> = ends.iter().enumerate().filter_map(|(index, ends)| {
+        ends.as_ref().map(|ends| Stop { index, entry: Entry::Forward, enter: ends.start, leave: ends.end })
+    }).collect();
+
+    let mut stops = Vec::with_capacity(paths.len());
+    let mut done = vec![false; paths.len()];
+    let mut at = pen;
+    loop {
+        let mut best: Option<(f64, Stop)> = None;
+        for (index, ends) in ends.iter().enumerate() {
+            let Some(ends) = ends.as_ref().filter(|_| !done[index]) else { continue };
+            let closer = |dist: f64, best: &Option<(f64, Stop)>| best.map_or(true, |(d, _)| dist < d);
+            if let Some((ref vertices, min, max)) = ends.vertices {
+                // Skip the vertices of loops that can't be any closer.
+                let nearest = at.max(min).min(max);
+                if !closer((nearest - at).length(), &best) {
+                    continue;
+                }
+                for (k, &vertex) in vertices.iter().enumerate() {
+                    let dist = (vertex - at).length();
+                    if closer(dist, &best) {
+                        best = Some((dist, Stop { index, entry: Entry::From(k), enter: vertex, leave: vertex }));
+                    }
+                }
+            } else {
+                let forward = Stop { index, entry: Entry::Forward, enter: ends.start, leave: ends.end };
+                for stop in [forward, forward.flipped()] {
+                    let dist = (stop.enter - at).length();
+                    if closer(dist, &best) {
+                        best = Some((dist, stop));
+                    }
+                }
+            }
+        }
+        let Some((_, stop)) = best else { break };
+        done[stop.index] = true;
+        at = stop.leave;
+        stops.push(stop);
+    }
+
+    if ordering == Ordering::TwoOpt {
+        two_opt(&mut stops, pen);
+    }
+    Plan {
+        travel_before: travel(&document, pen),
+        travel_after: travel(&stops, pen),
+        stops: stops.iter().map(|stop| (stop.index, stop.entry))
+            .chain((0..paths.len()).filter(|&index| !done[index]).map(|index| (index, Entry::Forward)))
+            .collect(),
+    }
+}
+
+/// Reverses runs of up to `MAX_RUN` stops, flipping each of them, while it
+/// shortens the travel.
+fn two_opt(stops: &mut [Stop], pen: Pt) {
+    let dist = |a: Pt, b: Pt| (b - a).length();
+    for _ in 0..MAX_PASSES {
+        let mut improved = false;
+        for i in 0..stops.len() {
+            let before = if i == 0 { pen } else { stops[i - 1].leave };
+            for j in i + 1..stops.len().min(i + MAX_RUN) {
+                let after = stops.get(j + 1).map(|stop| stop.enter);
+                let old = dist(before, stops[i].enter) + after.map_or(0.0, |p| dist(stops[j].leave, p));
+                let new = dist(before, stops[j].leave) + after.map_or(0.0, |p| dist(stops[i].enter, p));
+                if new < old - 1e-9 {
+                    stops[i..=j].reverse();
+                    for stop in &mut stops[i..=j] {
+                        *stop = stop.flipped();
+                    }
+                    improved = true;
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+}
+
+/// Pen-up travel to draw `stops` in order, starting from `pen`.
+fn travel(stops: &[Stop], pen: Pt) -> f64 {
+    let mut at = pen;
+    let mut total = 0.0;
+    for stop in stops {
+        total += (stop.enter - at).length();
+        at = stop.leave;
+    }
+    total
+}
+
+fn ends(path: &PathData, map: impl Fn(Pt) -> Pt) -> Option<Ends> {
+    let subpaths: Vec<_> = path.subpaths().map(|subpath| edges(subpath.iter())).collect();
+    let (first, last) = (subpaths.first()?, subpaths.last()?);
+    let end = last.1.last().map_or(last.0, |edge| edge.to);
+    let vertices = match subpaths[..] {
+        [(start, ref edges, true)] => {
+            let vertices: Vec<Pt> = std::iter::once(start)
+                .chain(edges[..edges.len() - 1].iter().map(|edge| edge.to))
+                .map(&map)
+                .collect();
+            let min = vertices.iter().fold(vertices[0], |a, &b| a.min(b));
+            let max = vertices.iter().fold(vertices[0], |a, &b| a.max(b));
+            Some((vertices, min, max))
+        }
+        _ => None,
+    };
+    Some(Ends { start: map(first.0), end: map(end), vertices })
+}
+
+/// A line, or a curve with control points, to `to`.
+#[derive(Debug, Clone, Copy)]
+pub struct Edge {
+    pub ctrl: Option<(Pt, Pt)>,
+    pub to: Pt,
+}
+
+/// The start, edges and whether a subpath is closed. Closed subpaths end
+/// with an edge back to their start.
+pub fn edges<'a>(segments: impl Iterator<Item = &'a PathSegment>) -> (Pt, Vec<Edge>, bool) {
+    let mut start = Pt::zero();
+    let mut edges = Vec::new();
+    let mut closed = false;
+    for seg in segments {
+        match *seg {
+            PathSegment::MoveTo { x, y } => start = Pt::new(x, y),
+            PathSegment::LineTo { x, y } => edges.push(Edge { ctrl: None, to: Pt::new(x, y) }),
+            PathSegment::CurveTo { x1, y1, x2, y2, x, y } => edges.push(Edge {
+                ctrl: Some((Pt::new(x1, y1), Pt::new(x2, y2))),
+                to: Pt::new(x, y),
+            }),
+            PathSegment::ClosePath => closed = true,
+        }
+    }
+    if closed && edges.last().map_or(true, |edge| edge.to != start) {
+        edges.push(Edge { ctrl: None, to: start });
+    }
+    (start, edges, closed)
+}
+
+/// The subpath from `start` along `edges` drawn backwards.
+pub fn reverse(start: Pt, edges: &[Edge]) -> (Pt, Vec<Edge>) {
+    let mut from = start;
+    let mut reversed = Vec::with_capacity(edges.len());
+    for edge in edges {
+        reversed.push(Edge { ctrl: edge.ctrl.map(|(c1, c2)| (c2, c1)), to: from });
+        from = edge.to;
+    }
+    reversed.reverse();
+    (from, reversed)
+}
+
+/// The closed subpath from `start` along `edges` started from vertex `k`.
+pub fn rotate(start: Pt, edges: &[Edge], k: usize) -> (Pt, Vec<Edge>) {
+    let from = if k == 0 { start } else { edges[k - 1].to };
+    (from, [&edges[k..], &edges[..k]].concat())
+}
+
+pub fn push_subpath(out: &mut PathData, start: Pt, edges: &[Edge], closed: bool) {
+    out.push_move_to(start.x, start.y);
+    // Closed subpaths leave their last line to the ClosePath.
+    let edges = match edges.split_last() {
+        Some((Edge { ctrl: None, .. }, rest)) if closed => rest,
+        _ => edges,
+    };
+    for edge in edges {
+        match edge.ctrl {
+            Some((c1, c2)) => out.push_curve_to(c1.x, c1.y, c2.x, c2.y, edge.to.x, edge.to.y),
+            None => out.push_line_to(edge.to.x, edge.to.y),
+        }
+    }
+    if closed {
+        out.push_close_path();
+    }
+}
+
+/// `path` drawn as `entry` says.
+pub fn apply(path: &PathData, entry: Entry) -> Cow<'_, PathData> {
+    let mut out = PathData::new();
+    match entry {
+        Entry::Forward => return Cow::Borrowed(path),
+        Entry::Reversed => {
+            let subpaths: Vec<_> = path.subpaths().map(|subpath| edges(subpath.iter())).collect();
+            for (start, edges, closed) in subpaths.into_iter().rev() {
+                let (from, reversed) = reverse(start, &edges);
+                push_subpath(&mut out, from, &reversed, closed);
+            }
+        }
+        Entry::From(k) => {
+            let Some(subpath) = path.subpaths().next() else { return Cow::Borrowed(path) };
+            let (start, edges, closed) = edges(subpath.iter());
+            let (from, rotated) = rotate(start, &edges, k);
+            push_subpath(&mut out, from, &rotated, closed);
+        }
+    }
+    Cow::Owned(out)
+}
@@ -0,0 +1,134 @@
+//! Random placement of points along paths, for generative art.
+
+use crate::rng::Rng;
+use crate::{flatten, Pt};
+use std::collections::HashMap;
+use usvg::PathData;
+
+/// Share of the target distance kept clear around blue noise points.
+/// Random sequential placement on a line jams at about 0.75 coverage, so
+/// this gives an average spacing close to the target.
+const BLUE_NOISE_RADIUS: f64 = 0.75;
+/// Candidates tried for every blue noise point before giving up.
+const ATTEMPTS: usize = 30;
+
+/// Splits `total` points between paths in proportion to `weights`, by
+/// placing every point uniformly at random along the combined weight.
+pub fn split_points(weights: &[f64], total: usize, rng: &mut Rng) -> Vec<usize> {
+    let mut cumulative = Vec::with_capacity(weights.len());
+    let mut sum = 0.0;
+    for &weight in weights {
+        sum += weight;
+        cumulative.push(sum);
+    }
+    let mut counts = vec![0; weights.len()];
+    if sum <= 0.0 {
+        return counts;
+    }
+    for _ in 0..total {
+        let at = rng.range(0.0, sum);
+        let path = cumulative.partition_point(|&end| end <= at).min(weights.len() - 1);
+        counts[path] += 1;
+    }
+    counts
+}
+
+pub struct Scatter {
+    blue_noise: bool,
+    /// Size of the cells points are bucketed by.
+    cell: f64,
+    /// Points placed so far, blue noise only.
+    placed: HashMap<(i64, i64), Vec<Pt>>,
+}
+
+impl Scatter {
+    /// With `blue_noise` points are kept apart from all points placed
+    /// before, on any path. `cell` should be close to the typical distance.
+    pub fn new(blue_noise: bool, cell: f64) -> Scatter {
+        Scatter { blue_noise, cell, placed: HashMap::new() }
+    }
+
+    /// Appends `count` random points along `path` to `out`, in the order
+    /// they appear along the path, with the index of their subpath and
+    /// how far along the flattened subpath they are. Blue noise points are
+    /// placed for an average spacing of about `distance`, and may be fewer
+    /// than `count` when the path is crowded.
+    pub fn scatter(
+        &mut self,
+        path: &PathData,
+        count: usize,
+        distance: f64,
+        accuracy: f64,
+        rng: &mut Rng,
+        out: &mut Vec<(Pt, usize, f64)>,
+    ) {
+        let lines: Vec<Vec<Pt>> = flatten(path, accuracy).into_iter().map(|(line, _)| line).collect();
+        let length: f64 = lines.iter().map(|line| polyline_length(line)).sum();
+        if count == 0 || length <= 0.0 {
+            return;
+        }
+
+        let radius = distance * BLUE_NOISE_RADIUS;
+        let mut positions: Vec<f64> = Vec::with_capacity(count);
+        let attempts = if self.blue_noise { count * ATTEMPTS } else { count };
+        for _ in 0..attempts {
+            if positions.len() == count {
+                break;
+            }
+            let at = rng.range(0.0, length);
+            if self.blue_noise {
+                let (pt, _, _) = point_at(&lines, at);
+                if !self.is_free(pt, radius) {
+                    continue;
+                }
+                self.placed.entry(cell(pt, self.cell)).or_default().push(pt);
+            }
+            positions.push(at);
+        }
+        positions.sort_by(f64::total_cmp);
+        out.extend(positions.iter().map(|&at| point_at(&lines, at)));
+    }
+
+    fn is_free(&self, pt: Pt, radius: f64) -> bool {
+        let (cx, cy) = cell(pt, self.cell);
+        let reach = (radius / self.cell).ceil() as i64;
+        for x in cx - reach..=cx + reach {
+            for y in cy - reach..=cy + reach {
+                let near = self.placed.get(&(x, y)).is_some_and(|pts| {
+                    pts.iter().any(|&other| (other - pt).square_length() < radius * radius)
+                });
+                if near {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+fn cell(pt: Pt, size: f64) -> (i64, i64) {
+    ((pt.x / size).floor() as i64, (pt.y / size).floor() as i64)
+}
+
+fn polyline_length(line: &[Pt]) -> f64 {
+    line.windows(2).map(|w| (w[1] - w[0]).length()).sum()
+}
+
+/// Point `at` along the polylines, laid end to end, with the index of its
+/// polyline and how far along that polyline it is.
+fn point_at(lines: &[Vec<Pt>], mut at: f64) -> (Pt, usize, f64) {
+    for (index, line) in lines.iter().enumerate() {
+        let mut along = 0.0;
+        for w in line.windows(2) {
+            let len = (w[1] - w[0]).length();
+            if at <= len && len > 0.0 {
+                return (w[0].lerp(w[1], at / len), index, along + at);
+            }
+            at -= len;
+            along += len;
+        }
+    }
+    let index = lines.len().saturating_sub(1);
+    let end = lines.last().and_then(|line| line.last()).copied().unwrap_or_default();
+    (end, index, lines.last().map_or(0.0, |line| polyline_length(line)))
+}
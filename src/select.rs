@@ -0,0 +1,69 @@
+//! Selection of the paths to convert by the ids of their elements and of
+//! the groups and Inkscape layers they are in.
+
+use crate::Ret;
+use std::collections::HashSet;
+use usvg::{Node, NodeExt, NodeKind, Tree};
+
+const INKSCAPE_NS: &str = "http://www.inkscape.org/namespaces/inkscape";
+
+/// Ids of the paths to keep and to leave out. A path matches an id when
+/// it or a group it is in has that id.
+#[derive(Debug, Default)]
+pub struct Selection {
+    pub select: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl Selection {
+    pub fn is_empty(&self) -> bool {
+        self.select.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn accepts(&self, node: &Node) -> bool {
+        let matches = |ids: &[String]| node.ancestors().any(|node| {
+            let id = node.id();
+            !id.is_empty() && ids.iter().any(|wanted| *wanted == *id)
+        });
+        (self.select.is_empty() || matches(&self.select)) && !matches(&self.exclude)
+    }
+
+    /// Fails on selected ids that are not in `svg`, so typos don't
+    /// silently give an empty output.
+    pub fn check(&self, svg: &Tree) -> Ret<()> {
+        let ids: HashSet<String> = svg.root().descendants()
+            .filter(|node| matches!(*node.borrow(), NodeKind::Path(_) | NodeKind::Group(_)))
+            .map(|node| node.id().to_string())
+            .collect();
+        match self.select.iter().find(|id| !ids.contains(*id)) {
+            Some(id) => Err(format!("no path or group with id '{}'", id).into()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Ids of the Inkscape layers of the svg `data` with one of the `labels`.
+pub fn layer_ids(data: &[u8], labels: &[String]) -> Ret<Vec<String>> {
+    let text = std::str::from_utf8(data).map_err(|_| "layers can only be read from uncompressed svg")?;
+    let xml_opt = roxmltree::ParsingOptions { allow_dtd: true };
+    let doc = roxmltree::Document::parse_with_options(text, xml_opt)
+        .map_err(|err| format!("{err}: Unable to parse svg"))?;
+
+    let mut ids = Vec::new();
+    for label in labels {
+        let mut found = false;
+        for node in doc.descendants() {
+            if node.attribute((INKSCAPE_NS, "groupmode")) == Some("layer")
+                && node.attribute((INKSCAPE_NS, "label")) == Some(label.as_str())
+            {
+                let id = node.attribute("id").ok_or_else(|| format!("layer '{}' has no id", label))?;
+                ids.push(id.to_string());
+                found = true;
+            }
+        }
+        if !found {
+            return Err(format!("no layer labelled '{}'", label).into());
+        }
+    }
+    Ok(ids)
+}
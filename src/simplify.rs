@@ -0,0 +1,156 @@
+//! Simplification of the sampled points of a subpath, dropping points
+//! that barely change its shape.
+
+use crate::Pt;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Simplify {
+    /// Ramer–Douglas–Peucker, keeps the points further than the tolerance
+    /// from the simplified line.
+    Rdp(f64),
+    /// Visvalingam–Whyatt, drops points whose triangle with their
+    /// neighbours is smaller than the area.
+    Area(f64),
+    /// Visvalingam–Whyatt, drops the smallest triangles until this many
+    /// points are left.
+    Count(usize),
+}
+
+/// Marks the points of `pts` to keep in `keep`. The first and last points
+/// are always kept. A `closed` subpath is simplified as a loop, whether or
+/// not its last point repeats the first, in which case only the first
+/// point is an end point.
+pub fn simplify(pts: &[Pt], closed: bool, method: Simplify, keep: &mut Vec<bool>) {
+    keep.clear();
+    keep.resize(pts.len(), true);
+    if pts.len() < 3 {
+        return;
+    }
+    // Close the loop so the points around the start are judged against
+    // their neighbours on both sides.
+    let open_loop = closed && pts[0] != pts[pts.len() - 1];
+    let ring;
+    let pts = if open_loop {
+        ring = [pts, &pts[..1]].concat();
+        keep.push(true);
+        &ring[..]
+    } else {
+        pts
+    };
+    keep.fill(false);
+    keep[0] = true;
+    keep[pts.len() - 1] = true;
+
+    match method {
+        Simplify::Rdp(tolerance) => rdp(pts, tolerance, keep),
+        Simplify::Area(area) => visvalingam(pts, area, 2, keep),
+        Simplify::Count(count) => visvalingam(pts, f64::INFINITY, count.max(2) + open_loop as usize, keep),
+    }
+    if open_loop {
+        keep.pop();
+    }
+}
+
+/// Distance from `pt` to the line segment `a`, `b`.
+pub fn segment_distance(pt: Pt, a: Pt, b: Pt) -> f64 {
+    let ab = b - a;
+    let len = ab.square_length();
+    if len == 0.0 {
+        return (pt - a).length();
+    }
+    let t = ((pt - a).dot(ab) / len).clamp(0.0, 1.0);
+    (pt - a.lerp(b, t)).length()
+}
+
+fn rdp(pts: &[Pt], tolerance: f64, keep: &mut [bool]) {
+    let mut stack = vec![(0, pts.len() - 1)];
+    while let Some((first, last)) = stack.pop() {
+        let mut max = 0.0;
+        let mut index = first;
+        for i in first + 1..last {
+            let dist = segment_distance(pts[i], pts[first], pts[last]);
+            if dist > max {
+                max = dist;
+                index = i;
+            }
+        }
+        if max > tolerance {
+            keep[index] = true;
+            stack.push((first, index));
+            stack.push((index, last));
+        }
+    }
+}
+
+fn triangle_area(a: Pt, b: Pt, c: Pt) -> f64 {
+    (b - a).cross(c - a).abs() / 2.0
+}
+
+/// A point in the heap, smallest area first.
+struct Candidate {
+    area: f64,
+    index: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.area.total_cmp(&self.area).then(other.index.cmp(&self.index))
+    }
+}
+
+/// Drops the point with the smallest effective area until it reaches
+/// `min_area` or only `min_count` points are left.
+fn visvalingam(pts: &[Pt], min_area: f64, min_count: usize, keep: &mut [bool]) {
+    let n = pts.len();
+    let mut prev: Vec<usize> = (0..n).map(|i| i.wrapping_sub(1)).collect();
+    let mut next: Vec<usize> = (1..=n).collect();
+    let mut area = vec![f64::INFINITY; n];
+    let mut heap = BinaryHeap::with_capacity(n);
+    for i in 1..n - 1 {
+        area[i] = triangle_area(pts[i - 1], pts[i], pts[i + 1]);
+        heap.push(Candidate { area: area[i], index: i });
+    }
+
+    let mut left = n;
+    let mut removed = vec![false; n];
+    let mut largest = 0.0_f64; // Effective areas never shrink
+    while let Some(Candidate { area: smallest, index }) = heap.pop() {
+        if removed[index] || smallest != area[index] {
+            continue; // Stale entry
+        }
+        if smallest >= min_area || left <= min_count {
+            break;
+        }
+        largest = largest.max(smallest);
+        removed[index] = true;
+        left -= 1;
+        let (p, q) = (prev[index], next[index]);
+        next[p] = q;
+        prev[q] = p;
+        for i in [p, q] {
+            if i != 0 && i != n - 1 {
+                area[i] = triangle_area(pts[prev[i]], pts[i], pts[next[i]]).max(largest);
+                heap.push(Candidate { area: area[i], index: i });
+            }
+        }
+    }
+    for (k, removed) in keep.iter_mut().zip(removed) {
+        *k = !removed;
+    }
+}
@@ -0,0 +1,270 @@
+//! Stroke styles applied to the geometry of a path: dashes, and expansion
+//! into the outline of the stroke so the visible shape of a thick line can
+//! be traced.
+//!
+//! Every subpath is expanded on its own and the resulting polygons are not
+//! merged, so sharp turns and self-crossing paths leave overlapping loops.
+
+use crate::{flatten, is_near, Pt};
+use kurbo::{CubicBez, Line, ParamCurve, ParamCurveArclen, PathSeg, Point};
+use usvg::{LineCap, LineJoin, PathData, PathSegment, Stroke};
+
+/// Splits `path` into a subpath for every dash of `dasharray`, starting
+/// `offset` into the pattern. The pattern restarts on every subpath.
+pub fn dashes(path: &PathData, dasharray: &[f64], offset: f64, accuracy: f64) -> PathData {
+    let mut pattern = dasharray.to_vec();
+    if pattern.len() % 2 == 1 {
+        pattern.extend_from_slice(dasharray);
+    }
+    let period: f64 = pattern.iter().sum();
+    if period <= 0.0 || pattern.iter().any(|&dash| dash < 0.0) {
+        return path.clone();
+    }
+
+    let mut out = PathData::new();
+    for subpath in path.subpaths() {
+        // Find where in the pattern the subpath starts.
+        let mut index = 0;
+        let mut left = offset.rem_euclid(period);
+        while left >= pattern[index] {
+            left -= pattern[index];
+            index = (index + 1) % pattern.len();
+        }
+        left = pattern[index] - left;
+        let mut drawing = false; // Inside a dash that has been started
+
+        let mut start = Point::ZERO;
+        let mut at = Point::ZERO;
+        for seg in subpath.iter() {
+            let curve = match *seg {
+                PathSegment::MoveTo { x, y } => {
+                    start = Point::new(x, y);
+                    at = start;
+                    continue;
+                }
+                PathSegment::LineTo { x, y } => PathSeg::Line(Line::new(at, (x, y))),
+                PathSegment::ClosePath => PathSeg::Line(Line::new(at, start)),
+                PathSegment::CurveTo { x1, y1, x2, y2, x, y } => {
+                    PathSeg::Cubic(CubicBez::new(at, Point::new(x1, y1), Point::new(x2, y2), Point::new(x, y)))
+                }
+            };
+            at = curve.end();
+
+            let length = curve.arclen(accuracy);
+            let mut pos = 0.0;
+            let mut t = 0.0;
+            loop {
+                let take = left.min(length - pos);
+                let end_t = if pos + take >= length {
+                    1.0
+                } else {
+                    curve.inv_arclen(pos + take, accuracy)
+                };
+                if index % 2 == 0 {
+                    if !drawing {
+                        let from = curve.eval(t);
+                        out.push_move_to(from.x, from.y);
+                        drawing = true;
+                    }
+                    match curve.subsegment(t..end_t) {
+                        PathSeg::Cubic(piece) => out.push_curve_to(
+                            piece.p1.x, piece.p1.y, piece.p2.x, piece.p2.y, piece.p3.x, piece.p3.y
+                        ),
+                        piece => out.push_line_to(piece.end().x, piece.end().y),
+                    }
+                }
+                pos += take;
+                left -= take;
+                t = end_t;
+                if left > 1e-9 {
+                    break;
+                }
+                index = (index + 1) % pattern.len();
+                left = pattern[index];
+                drawing = false;
+                if pos >= length {
+                    break;
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Outline of the stroke of `path`, one closed subpath for every open
+/// subpath and two, outside and inside, for every closed one.
+pub fn outline(path: &PathData, stroke: &Stroke, accuracy: f64) -> PathData {
+    let stroker = Stroker {
+        half: stroke.width.value() / 2.0,
+        join: stroke.linejoin,
+        cap: stroke.linecap,
+        miter_limit: stroke.miterlimit.value(),
+        accuracy,
+    };
+    let mut out = PathData::new();
+    let mut side = Vec::new();
+    for (mut line, closed) in flatten(path, accuracy) {
+        line.dedup_by(|a, b| is_near(*a, *b));
+        if closed && line.len() > 1 && is_near(line[0], line[line.len() - 1]) {
+            line.pop();
+        }
+
+        side.clear();
+        if line.len() == 1 {
+            stroker.dot(line[0], &mut side);
+            push_polygon(&side, &mut out);
+        } else if closed && line.len() > 2 {
+            stroker.offset(&line, true, &mut side);
+            push_polygon(&side, &mut out);
+            side.clear();
+            line.reverse();
+            stroker.offset(&line, true, &mut side);
+            push_polygon(&side, &mut out);
+        } else if line.len() > 1 {
+            let n = line.len();
+            stroker.offset(&line, false, &mut side);
+            stroker.cap(line[n - 1], direction(line[n - 2], line[n - 1]), &mut side);
+            line.reverse();
+            stroker.offset(&line, false, &mut side);
+            stroker.cap(line[n - 1], direction(line[n - 2], line[n - 1]), &mut side);
+            push_polygon(&side, &mut out);
+        }
+    }
+    out
+}
+
+fn push_polygon(polygon: &[Pt], out: &mut PathData) {
+    if let Some((first, rest)) = polygon.split_first() {
+        out.push_move_to(first.x, first.y);
+        for pt in rest {
+            out.push_line_to(pt.x, pt.y);
+        }
+        out.push_close_path();
+    }
+}
+
+fn direction(from: Pt, to: Pt) -> Pt {
+    (to - from).normalize()
+}
+
+/// Left hand normal of the unit `dir`, svg y is down so this is the right
+/// hand side as the drawing is seen.
+fn normal(dir: Pt) -> Pt {
+    Pt::new(-dir.y, dir.x)
+}
+
+struct Stroker {
+    half: f64,
+    join: LineJoin,
+    cap: LineCap,
+    miter_limit: f64,
+    accuracy: f64,
+}
+
+impl Stroker {
+    /// Pushes the left hand offset of `line`, joining at every vertex.
+    /// Closed lines are joined back to the start as well.
+    fn offset(&self, line: &[Pt], closed: bool, out: &mut Vec<Pt>) {
+        let n = line.len();
+        let seg = |i: usize| (line[i], line[(i + 1) % n]);
+        if !closed {
+            let (a, b) = seg(0);
+            out.push(a + normal(direction(a, b)) * self.half);
+        }
+        let vertices = if closed { 0..n } else { 1..n - 1 };
+        for i in vertices {
+            let (a, p) = seg((i + n - 1) % n);
+            let (_, b) = seg(i);
+            self.join(p, direction(a, p), direction(p, b), (p - a).length().min((b - p).length()), out);
+        }
+        if !closed {
+            let (a, b) = seg(n - 2);
+            out.push(b + normal(direction(a, b)) * self.half);
+        }
+    }
+
+    /// Pushes the left hand side of the join at `p` from the incoming unit
+    /// direction `d0` to the outgoing `d1`. `room` is the length of the
+    /// shorter of the two segments.
+    fn join(&self, p: Pt, d0: Pt, d1: Pt, room: f64, out: &mut Vec<Pt>) {
+        let (n0, n1) = (normal(d0) * self.half, normal(d1) * self.half);
+        let turn = d0.cross(d1);
+        let cos = d0.dot(d1);
+        if turn.abs() < 1e-12 && cos > 0.0 { // Straight on
+            out.push(p + n0);
+            return;
+        }
+        // Where the two offset lines meet, on either side.
+        let miter = (n0 + n1) * (self.half * self.half / (self.half * self.half + n0.dot(n1)));
+
+        if turn > 0.0 { // Left side is the inside of the turn
+            if cos > -0.999 && (miter - n0).length() <= room {
+                out.push(p + miter);
+            } else {
+                out.push(p + n0);
+                out.push(p + n1);
+            }
+            return;
+        }
+
+        match self.join {
+            LineJoin::Miter if 1.0 / ((1.0 + cos) / 2.0).sqrt() <= self.miter_limit => {
+                out.push(p + miter);
+            }
+            LineJoin::Round => {
+                out.push(p + n0);
+                self.arc(p, n0, n0.cross(n1).atan2(n0.dot(n1)), out);
+                out.push(p + n1);
+            }
+            _ => { // Bevel, also used past the miter limit
+                out.push(p + n0);
+                out.push(p + n1);
+            }
+        }
+    }
+
+    /// Pushes the cap at the end `p` of a line going in the unit direction
+    /// `dir`, between the left and right hand offsets.
+    fn cap(&self, p: Pt, dir: Pt, out: &mut Vec<Pt>) {
+        let n = normal(dir) * self.half;
+        match self.cap {
+            LineCap::Butt => (),
+            LineCap::Square => {
+                out.push(p + n + dir * self.half);
+                out.push(p - n + dir * self.half);
+            }
+            LineCap::Round => {
+                self.arc(p, n, -std::f64::consts::PI, out);
+            }
+        }
+    }
+
+    /// Pushes the caps of a zero length subpath, which are still drawn.
+    fn dot(&self, p: Pt, out: &mut Vec<Pt>) {
+        let dir = Pt::new(1.0, 0.0);
+        match self.cap {
+            LineCap::Butt => (),
+            LineCap::Square => {
+                self.cap(p, -dir, out);
+                self.cap(p, dir, out);
+            }
+            LineCap::Round => {
+                out.push(p + normal(dir) * self.half);
+                self.arc(p, normal(dir) * self.half, -std::f64::consts::TAU, out);
+            }
+        }
+    }
+
+    /// Pushes the points of an arc around `center` starting at the offset
+    /// `from` and turning by `sweep` radians, without its end points.
+    fn arc(&self, center: Pt, from: Pt, sweep: f64, out: &mut Vec<Pt>) {
+        let max_step = 2.0 * (1.0 - self.accuracy / self.half).clamp(-1.0, 1.0).acos();
+        let steps = (sweep.abs() / max_step.max(1e-3)).ceil().max(1.0) as usize;
+        let (sin, cos) = (sweep / steps as f64).sin_cos();
+        let mut offset = from;
+        for _ in 1..steps {
+            offset = Pt::new(offset.x * cos - offset.y * sin, offset.x * sin + offset.y * cos);
+            out.push(center + offset);
+        }
+    }
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::len_zero, clippy::needless_borrow, clippy::needless_borrows_for_generic_args, clippy::neg_cmp_op_on_partial_ord, clippy::redundant_static_lifetimes)]
+
 use tempfile::NamedTempFile;
 use std::io::{Read, Seek, SeekFrom};
 use assert_cmd::Command;
@@ -224,3 +226,966 @@ fn points_target_with_complex_svg() {
     check_pts(0.1, 0.01, &["-p", "2000","tests/data/complex.svg"], &pts, 1900..2100);
 }
 
+
+static DATA_TRIANGLE: &str = r#"<svg viewBox="0 0 10 10" xmlns="http://www.w3.org/2000/svg">
+<path stroke="black" fill="none" d="M 0 0 L 10 0 L 10 4 Z"/>
+</svg>"#;
+
+#[test]
+fn segment_sampling_keeps_endpoints() {
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    let assert = cmd.args(["-d", "3", "-s", "segments"]).write_stdin(DATA_TRIANGLE).assert();
+    assert.stdout(predicate::function(|out: &str| {
+        let p = extract_pts(out).unwrap();
+        // 1 start + 3 + 1 + 4 parts
+        assert_eq!(p.len(), 9);
+        for corner in [Pt::new(10.0, 10.0), Pt::new(10.0, 6.0), Pt::new(0.0, 10.0)] {
+            assert!(p.iter().any(|&pt| (pt - corner).length() < 1e-9));
+        }
+        let d = (p[1] - p[0]).length();
+        assert_lt!((d - 10.0/3.0).abs(), 1e-9);
+        assert_lt!(((p[2] - p[1]).length() - d).abs(), 1e-9);
+        true
+    })).success();
+}
+
+static DATA_CIRCLE: &str = r#"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg">
+<circle cx="50" cy="50" r="20" stroke="black" fill="none"/>
+</svg>"#;
+
+#[test]
+fn seamless_closed_path() {
+    for sampling in ["distance", "segments"] {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        let assert = cmd.args(["-d", "7", "-s", sampling, "--seamless"]).write_stdin(DATA_CIRCLE).assert();
+        assert.stdout(predicate::function(|out: &str| {
+            let mut p = extract_pts(out).unwrap();
+            let first = p[0];
+            p.push(first);
+            let gaps: Vec<f64> = p.windows(2).map(|w| (w[1] - w[0]).length()).collect();
+            let mean = gaps.iter().sum::<f64>() / gaps.len() as f64;
+            assert!(gaps.iter().all(|gap| (gap - mean).abs() < 0.02 * mean));
+            true
+        })).success();
+    }
+}
+
+#[test]
+fn tangent_normal_curvature() {
+    for args in [&["-d", "0"][..], &["-d", "3"], &["-d", "3", "-s", "segments"]] {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        let assert = cmd.args(args).args(["--tangents", "--normals", "--curvature"])
+            .write_stdin(DATA_CIRCLE).assert();
+        assert.stdout(predicate::function(|out: &str| {
+            for line in out.lines() {
+                let v: Vec<f64> = line.split(' ').map(|x| x.parse().unwrap()).collect();
+                assert_eq!(v.len(), 7);
+                let (radius, tangent, normal) = (Pt::new(v[0] - 50.0, v[1] - 50.0),
+                                                 Pt::new(v[2], v[3]), Pt::new(v[4], v[5]));
+                assert_lt!((tangent.length() - 1.0).abs(), 1e-9);
+                assert_lt!(tangent.dot(radius).abs(), 0.1);
+                assert_eq!(normal, Pt::new(-tangent.y, tangent.x));
+                // Clockwise as the flipped output is seen, the normal points out.
+                assert_lt!((v[6] + 1.0 / 20.0).abs(), 2e-3);
+                assert_lt!(0.0, normal.dot(radius));
+            }
+            true
+        })).success();
+    }
+
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    let assert = cmd.args(["--curvature"]).write_stdin(DATA_LINE_AND_SQUARE).assert();
+    assert.stdout(predicate::function(|out: &str| {
+        out.lines().all(|line| line.split(' ').nth(2) == Some("0.0"))
+    })).success();
+
+    // x runs along t, so every point is checked against the true cubic,
+    // flipped as y points up in the output.
+    let cubic = r#"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg">
+<path stroke="black" fill="none" d="M 0 0 C 30 90 60 -60 90 30"/></svg>"#;
+    let y = |t: f64| 270.0 * (1.0 - t) * (1.0 - t) * t - 180.0 * (1.0 - t) * t * t + 30.0 * t * t * t;
+    let dy = |t: f64| 270.0 * (1.0 - t) * (1.0 - 3.0 * t) - 180.0 * t * (2.0 - 3.0 * t) + 90.0 * t * t;
+    for args in [&["-d", "0"][..], &["-d", "0", "--tangents"]] {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        let assert = cmd.args(args).write_stdin(cubic).assert();
+        assert.stdout(predicate::function(|out: &str| {
+            for line in out.lines() {
+                let v: Vec<f64> = line.split(' ').map(|x| x.parse().unwrap()).collect();
+                let t = v[0] / 90.0;
+                assert_lt!((v[1] - 100.0 + y(t)).abs(), 1e-9);
+                if let [_, _, tx, ty] = v[..] {
+                    assert_lt!((Pt::new(tx, ty) - Pt::new(90.0, -dy(t)).normalize()).length(), 1e-9);
+                }
+            }
+            true
+        })).success();
+    }
+}
+
+#[test]
+fn simplification() {
+    let run = |args: &[&str], data: &str| {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        let out = cmd.args(args).write_stdin(data.to_string()).assert().success()
+            .get_output().stdout.clone();
+        extract_pts(&String::from_utf8(out).unwrap().replace("\n\n", "\n")).unwrap()
+    };
+    let full = run(&["-d", "1"], DATA_CIRCLE);
+    let rdp = run(&["-d", "1", "--rdp", "0.1"], DATA_CIRCLE);
+    assert_lt!(rdp.len(), full.len() / 2);
+    assert_eq!(rdp[0], full[0]);
+    let ring: Vec<Pt> = rdp.iter().chain(&rdp[..1]).copied().collect();
+    for &pt in &full {
+        let dist = ring.windows(2).map(|w| {
+            let t = ((pt - w[0]).dot(w[1] - w[0]) / (w[1] - w[0]).square_length()).clamp(0.0, 1.0);
+            (pt - w[0].lerp(w[1], t)).length()
+        }).fold(f64::INFINITY, f64::min);
+        assert_lt!(dist, 0.1 + 1e-9);
+    }
+
+    let count = run(&["-d", "1", "--seamless", "--visvalingam-points", "6"], DATA_CIRCLE);
+    assert_eq!(count.len(), 6);
+    assert_eq!(count[0], full[0]);
+    assert!(count.iter().all(|pt| full.contains(pt) || (*pt - Pt::new(50.0, 50.0)).length() > 19.9));
+
+    let line = r#"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg">
+<path stroke="black" d="M 0 50 H 10 H 20 H 30 M 0 60 H 10 L 10.001 60.5 H 30"/>
+</svg>"#;
+    let p = run(&["-b", "--rdp", "0"], line);
+    assert_eq!(p, [Pt::new(0.0, 50.0), Pt::new(30.0, 50.0),
+                   Pt::new(0.0, 40.0), Pt::new(10.0, 40.0), Pt::new(10.001, 39.5), Pt::new(30.0, 39.5)]);
+    let p = run(&["--visvalingam", "3"], line); // Only the triangle at (10, 40) is smaller
+    assert_eq!(p.len(), 5);
+}
+
+static DATA_LINE_AND_SQUARE: &str = r#"<svg viewBox="0 0 1000 100" xmlns="http://www.w3.org/2000/svg">
+<path stroke="black" d="M 0 50 L 1000 50"/>
+<path stroke="black" fill="none" d="M 10 10 h 2 v 2 h -2 Z"/>
+</svg>"#;
+
+#[test]
+fn points_allocation() {
+    // (allocation, total points, points on the square)
+    let cases = [("length", 95..106, 0..3), ("minimum", 95..106, 8..12), ("equal", 95..106, 45..52)];
+    for (allocation, total, square) in cases {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        let assert = cmd.args(["-p", "100", "--allocation", allocation, "--min-points", "10"])
+            .write_stdin(DATA_LINE_AND_SQUARE).assert();
+        assert.stdout(predicate::function(|out: &str| {
+            let p = extract_pts(out).unwrap();
+            assert_range!(total, p.len());
+            assert_range!(square, p.iter().filter(|pt| pt.y > 80.0).count());
+            true
+        })).success();
+    }
+}
+
+#[test]
+fn random_sampling() {
+    let run = |sampling: &str, seed: &str| {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        let out = cmd.args(["-d", "10", "-s", sampling, "--seed", seed])
+            .write_stdin(DATA_LINE_AND_SQUARE).assert().success().get_output().stdout.clone();
+        extract_pts(&String::from_utf8(out).unwrap()).unwrap()
+    };
+    for sampling in ["random", "blue-noise"] {
+        let p = run(sampling, "1");
+        assert_eq!(p, run(sampling, "1"));
+        assert_ne!(p, run(sampling, "2"));
+        // Every point is on the line or the square, weighted by length.
+        assert!(p.iter().all(|pt| pt.y == 50.0 || (pt.x >= 10.0 && pt.x <= 12.0)));
+        assert_range!(85..101, p.iter().filter(|pt| pt.y == 50.0).count());
+        assert!(p.windows(2).all(|w| w[0].y != 50.0 || w[1].y != 50.0 || w[0].x <= w[1].x));
+        if sampling == "blue-noise" {
+            let gaps = p.windows(2).filter(|w| w[0].y == 50.0 && w[1].y == 50.0);
+            assert!(gaps.map(|w| w[1].x - w[0].x).all(|gap| gap >= 7.5 - 1e-9));
+        } else {
+            assert_eq!(p.len(), 101); // 1008 long
+        }
+    }
+
+    // Scattered points get the columns of the point of the path they are on.
+    for sampling in ["random", "blue-noise"] {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        let assert = cmd.args(["-d", "3", "-s", sampling, "--tangents", "--curvature", "--arc-length"])
+            .write_stdin(DATA_CIRCLE).assert();
+        assert.stdout(predicate::function(|out: &str| {
+            let rows: Vec<Vec<f64>> = out.lines()
+                .map(|line| line.split(' ').map(|x| x.parse().unwrap()).collect())
+                .collect();
+            assert_lt!(20, rows.len());
+            for v in &rows {
+                assert_eq!(v.len(), 7);
+                let (radius, tangent) = (Pt::new(v[0] - 50.0, v[1] - 50.0), Pt::new(v[2], v[3]));
+                assert_lt!((tangent.length() - 1.0).abs(), 1e-9);
+                assert_lt!(tangent.dot(radius).abs(), 0.1);
+                assert_lt!((v[4] + 1.0 / 20.0).abs(), 2e-3);
+            }
+            for w in rows.windows(2) {
+                let (a, b) = (Pt::new(w[0][0] - 50.0, w[0][1] - 50.0), Pt::new(w[1][0] - 50.0, w[1][1] - 50.0));
+                let arc = 20.0 * a.cross(b).abs().atan2(a.dot(b));
+                assert_lt!(((w[1][5] - w[0][5]) - arc).abs(), 0.05);
+            }
+            true
+        })).success();
+    }
+}
+
+#[test]
+fn arc_length_and_time() {
+    for sampling in ["distance", "segments"] {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        let assert = cmd.args(["-d", "10", "-s", sampling, "--arc-length", "--speed", "4"])
+            .write_stdin(DATA_LINE_AND_SQUARE).assert();
+        assert.stdout(predicate::function(|out: &str| {
+            let rows: Vec<Vec<f64>> = out.lines()
+                .map(|line| line.split(' ').map(|x| x.parse().unwrap()).collect())
+                .collect();
+            let (line, square) = rows.split_at(rows.iter().position(|v| v[1] > 80.0).unwrap());
+            for v in line {
+                assert_eq!(v.len(), 5);
+                assert_lt!((v[2] - v[0]).abs(), 1e-9);
+                assert_eq!(v[2], v[3]);
+                assert_lt!((v[4] - v[3] / 4.0).abs(), 1e-9);
+            }
+            assert_lt!(1000.0 - line.last().unwrap()[2], 10.0 + 1e-9);
+            // The square starts over along its own path, but not the document.
+            assert_eq!(&square[0][2..], &[0.0, 1000.0, 250.0]);
+            assert!(square.windows(2).all(|w| w[0][2] < w[1][2]));
+            if sampling == "segments" { // Keeps the corners, so reaches the end
+                assert_lt!((square.last().unwrap()[3] - 1008.0).abs(), 1e-9);
+            }
+            true
+        })).success();
+    }
+}
+
+static DATA_OFFSET_VIEWBOX: &str = r#"<svg viewBox="10 20 100 50" xmlns="http://www.w3.org/2000/svg">
+<path stroke="black" d="M 30 30 L 60 40"/>
+</svg>"#;
+
+static DATA_DOTS: &str = r#"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg">
+<path stroke="black" d="M 10 20 L 10 20"/>
+<path stroke="black" d="M 30 60 Z"/>
+</svg>"#;
+
+#[test]
+fn coordinate_frame() {
+    let cases: [(&[&str], [f64; 4]); 6] = [
+        (&[], [20.0, 40.0, 50.0, 30.0]),
+        (&["--origin", "bottom-left"], [20.0, 40.0, 50.0, 30.0]),
+        (&["--y-down"], [20.0, 10.0, 50.0, 20.0]),
+        (&["--y-down", "--origin", "bottom-left"], [20.0, -40.0, 50.0, -30.0]),
+        (&["--origin", "center"], [-30.0, 15.0, 0.0, 5.0]),
+        (&["--origin", "bounds"], [0.0, 10.0, 30.0, 0.0]),
+    ];
+    for (args, expected) in cases {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        let assert = cmd.args(args).write_stdin(DATA_OFFSET_VIEWBOX).assert();
+        assert.stdout(predicate::function(|out: &str| {
+            let p = extract_pts(out).unwrap();
+            assert_eq!([p[0].x, p[0].y, p[1].x, p[1].y], expected, "{:?}", args);
+            true
+        })).success();
+    }
+
+    // Directions follow the y axis.
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    let assert = cmd.args(["--y-down", "--tangents"]).write_stdin(DATA_OFFSET_VIEWBOX).assert();
+    assert.stdout(predicate::function(|out: &str| {
+        out.lines().all(|line| line.split(' ').nth(3).unwrap().parse::<f64>().unwrap() > 0.0)
+    })).success();
+
+    // Dots count towards the bounds.
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    let assert = cmd.args(["--origin", "bounds"]).write_stdin(DATA_DOTS).assert();
+    assert.stdout(predicate::function(|out: &str| {
+        let p = extract_pts(out).unwrap();
+        assert_eq!([p[0].x, p[0].y, p[p.len() - 1].x, p[p.len() - 1].y], [0.0, 40.0, 20.0, 0.0]);
+        true
+    })).success();
+}
+
+#[test]
+fn fit_and_scale() {
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        let out = cmd.args(args).write_stdin(DATA_OFFSET_VIEWBOX).assert().success()
+            .get_output().stdout.clone();
+        extract_pts(&String::from_utf8(out).unwrap()).unwrap()
+    };
+    let near = |a: &[Pt], b: &[(f64, f64)]| {
+        a.len() == b.len() && a.iter().zip(b).all(|(&a, &b)| (a - Pt::from(b)).length() < 1e-9)
+    };
+    assert!(near(&run(&["--fit", "-1,-1,1,1"]), &[(-1.0, 1.0 / 3.0), (1.0, -1.0 / 3.0)]));
+    assert!(near(&run(&["--fit", "-1,-1,1,1", "--stretch"]), &[(-1.0, 1.0), (1.0, -1.0)]));
+    assert!(near(&run(&["--fit", "0,0,10,10", "--margin", "1", "--fit-view-box", "--y-down"]),
+                 &[(2.6, 3.8), (5.0, 4.6)]));
+    assert!(near(&run(&["--scale", "2,-1"]), &[(40.0, -40.0), (100.0, -30.0)]));
+
+    // Distances are in svg units unless scaling first.
+    let args = ["--fit", "0,0,300,300", "-d", "100", "-s", "segments"];
+    assert_eq!(run(&args).len(), 2);
+    let p = run(&[&args[..], &["--scale-first"]].concat());
+    assert!(near(&p, &[(0.0, 200.0), (100.0, 200.0 - 100.0 / 3.0), (200.0, 100.0 + 100.0 / 3.0), (300.0, 100.0)]));
+
+    // Paths of only dots fit too.
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    let out = cmd.args(["--fit", "0,0,10,10"]).write_stdin(DATA_DOTS).assert().success()
+        .get_output().stdout.clone();
+    let p = extract_pts(&String::from_utf8(out).unwrap()).unwrap();
+    assert!(near(&p, &[(2.5, 10.0), (2.5, 10.0), (7.5, 0.0), (7.5, 0.0)]));
+}
+
+#[test]
+fn command_line_transform() {
+    let grouped = r#"<svg viewBox="10 20 100 50" xmlns="http://www.w3.org/2000/svg">
+<g transform="translate(10 0)"><path stroke="black" d="M 20 30 L 50 40"/></g>
+</svg>"#;
+    let cases: [(&[&str], [f64; 4]); 5] = [
+        (&["--translate", "5,-5"], [25.0, 45.0, 55.0, 35.0]),
+        (&["--transform", "matrix(1 0 0 1 5 -5)"], [25.0, 45.0, 55.0, 35.0]),
+        (&["--rotate", "90,30,30"], [20.0, 40.0, 10.0, 10.0]),
+        // In the order given, unlike a transform list.
+        (&["--rotate", "90", "--translate", "5,-5"], [-35.0, 45.0, -45.0, 15.0]),
+        (&["--transform", "rotate(90) translate(5 -5)"], [-35.0, 35.0, -45.0, 5.0]),
+    ];
+    for data in [DATA_OFFSET_VIEWBOX, grouped] {
+        for (args, expected) in cases {
+            let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+            let assert = cmd.args(args).write_stdin(data).assert();
+            assert.stdout(predicate::function(|out: &str| {
+                let p = extract_pts(out).unwrap();
+                let got = [p[0].x, p[0].y, p[1].x, p[1].y];
+                got.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-9)
+            })).success();
+        }
+    }
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    cmd.args(["--transform", "skew(1)"]).write_stdin(DATA_OFFSET_VIEWBOX).assert().failure();
+}
+
+#[test]
+fn physical_units() {
+    let mm = r#"<svg width="100mm" height="50mm" viewBox="0 0 200 100" xmlns="http://www.w3.org/2000/svg">
+<path stroke="black" d="M 0 50 H 200"/>
+</svg>"#;
+    let px = r#"<svg width="96" height="48" viewBox="0 0 96 48" xmlns="http://www.w3.org/2000/svg">
+<path stroke="black" d="M 0 24 H 96"/>
+</svg>"#;
+    let run = |args: &[&str], data: &str| {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        let out = cmd.args(args).write_stdin(data.to_string()).assert().success()
+            .get_output().stdout.clone();
+        extract_pts(&String::from_utf8(out).unwrap()).unwrap()
+    };
+    let near = |a: Pt, b: (f64, f64)| (a - Pt::from(b)).length() < 1e-9;
+
+    let p = run(&["--units", "mm"], mm);
+    assert!(near(p[0], (0.0, 25.0)) && near(p[1], (100.0, 25.0)));
+    // 10mm is 20 user units, and 0.5in in inches.
+    assert_eq!(run(&["-d", "10mm"], mm).len(), 11);
+    assert!(near(run(&["-d", "10mm"], mm)[1], (20.0, 50.0)));
+    // The last point survives the conversion of the distance.
+    let p = run(&["-d", "10", "--units", "mm"], mm);
+    assert_eq!(p.len(), 11);
+    assert!(near(p[10], (100.0, 25.0)) && near(p[1], (10.0, 25.0)));
+    assert_eq!(run(&["-d", "10mm", "--units", "mm"], mm).len(), 11);
+    assert!(near(run(&["-d", "0.5", "--units", "in"], mm)[1], (0.5, 25.0 / 25.4)));
+    assert!(near(run(&["-d", "10mm", "--units", "in"], mm)[1], (10.0 / 25.4, 25.0 / 25.4)));
+
+    // Pixels depend on the resolution, millimetres do not.
+    assert!(near(run(&["--units", "in"], px)[1], (1.0, 0.25)));
+    assert!(near(run(&["--units", "in", "--dpi", "192"], px)[1], (0.5, 0.125)));
+    assert!(near(run(&["--units", "mm", "--dpi", "300"], mm)[1], (100.0, 25.0)));
+
+    // Other lengths take units too. Transforms apply in user units, the
+    // rest in output units.
+    assert!(near(run(&["--translate", "10mm,0"], mm)[0], (20.0, 50.0)));
+    assert!(near(run(&["--translate", "10mm,0", "--units", "mm"], mm)[0], (10.0, 25.0)));
+    assert!(near(run(&["--rotate", "180,50mm,25mm", "--units", "mm"], mm)[0], (100.0, 25.0)));
+    let p = run(&["--fit", "0,0,1in,1in", "--margin", "0.5in", "--units", "mm"], mm);
+    assert!(near(p[0], (12.7, 12.7)) && near(p[1], (12.7, 12.7)));
+    assert!(near(run(&["--quantize", "1in", "--units", "mm"], mm)[0], (0.0, 25.4)));
+    assert_eq!(run(&["--infill", "grid", "--infill-spacing", "10mm", "--no-outline"], &mm.replace("stroke", "fill")
+        .replace("M 0 50 H 200", "M 0 0 H 200 V 100 H 0 Z")).len(), 50);
+
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    cmd.args(["-d", "3furlong"]).write_stdin(mm).assert().failure();
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    cmd.args(["--rotate", "90mm"]).write_stdin(mm).assert().failure();
+}
+
+static DATA_SQUARE_WITH_HOLE: &str = r#"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg">
+<path fill="black" fill-rule="evenodd" d="M 10 10 h 80 v 80 h -80 Z M 30 30 h 40 v 40 h -40 Z"/>
+</svg>"#;
+
+#[test]
+fn infill_respects_fill_rule() {
+    let in_hole = |pt: &&Pt| pt.x > 31.0 && pt.x < 69.0 && pt.y > 31.0 && pt.y < 69.0;
+    for infill in ["grid", "jitter", "poisson"] {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        let assert = cmd.args(["--infill", infill, "--infill-spacing", "5"])
+            .write_stdin(DATA_SQUARE_WITH_HOLE).assert();
+        assert.stdout(predicate::function(|out: &str| {
+            let p = extract_pts(out).unwrap();
+            assert_range!(100..400, p.len());
+            assert_eq!(p.iter().filter(in_hole).count(), 0);
+            true
+        })).success();
+    }
+
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    let assert = cmd.args(["--infill", "grid", "--infill-spacing", "5"])
+        .write_stdin(DATA_SQUARE_WITH_HOLE.replace("evenodd", "nonzero")).assert();
+    assert.stdout(predicate::function(|out: &str| {
+        let p = extract_pts(out).unwrap();
+        assert_eq!(p.iter().filter(in_hole).count(), 49);
+        true
+    })).success();
+}
+
+#[test]
+fn hatch_infill() {
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    let assert = cmd.args(["--infill", "hatch", "--infill-spacing", "10", "--hatch-angle", "0", "--no-outline"])
+        .write_stdin(DATA_SQUARE_WITH_HOLE).assert();
+    assert.stdout(predicate::function(|out: &str| {
+        let p = extract_pts(out).unwrap();
+        // 8 rows, the 4 crossing the hole are split in two
+        assert_eq!(p.len(), 2 * (8 + 4));
+        for line in p.chunks(2) {
+            assert_eq!(line[0].y, line[1].y);
+            assert_eq!(line[0].y % 10.0, 0.0);
+            let mid = (line[0] + line[1]) / 2.0;
+            assert!(!(mid.x > 30.0 && mid.x < 70.0 && mid.y > 30.0 && mid.y < 70.0));
+        }
+        true
+    })).success();
+
+    // The spacing defaults to the distance from the point budget, 480 / 48.
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        cmd.args(["--infill", "hatch", "--hatch-angle", "0", "--no-outline"]).args(args)
+            .write_stdin(DATA_SQUARE_WITH_HOLE).assert().success().get_output().stdout.clone()
+    };
+    assert_eq!(run(&["-p", "48"]), run(&["-d", "10"]));
+}
+
+#[test]
+fn concentric_and_spiral_infill() {
+    let rect = r#"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg">
+<path fill="black" d="M 10 10 h 80 v 60 h -80 Z"/></svg>"#;
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    let assert = cmd.args(["--infill", "concentric", "--infill-spacing", "10", "--no-outline"])
+        .write_stdin(rect).assert();
+    assert.stdout(predicate::function(|out: &str| {
+        let p = extract_pts(out).unwrap();
+        assert!(!p.is_empty());
+        for pt in p {
+            // Output is flipped, the rectangle spans y 30..90
+            let inset = (pt.x - 10.0).min(90.0 - pt.x).min(pt.y - 30.0).min(90.0 - pt.y);
+            // The last loop collapses onto the middle line of the rectangle
+            assert!([10.0, 20.0, 30.0].iter().any(|level| (inset - level).abs() < 0.01), "{:?}", pt);
+        }
+        true
+    })).success();
+
+    let circle = r#"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg">
+<circle cx="50" cy="50" r="40" fill="black"/></svg>"#;
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    let assert = cmd.args(["--infill", "spiral", "--infill-spacing", "5", "--no-outline"])
+        .write_stdin(circle).assert();
+    assert.stdout(predicate::function(|out: &str| {
+        let radius: Vec<f64> = extract_pts(out).unwrap().iter()
+            .map(|&pt| (pt - Pt::new(50.0, 50.0)).length())
+            .collect();
+        assert_lt!((radius[0] - 35.0).abs(), 0.5);
+        assert_lt!((radius[radius.len() - 1] - 5.0).abs(), 0.5);
+        assert!(radius.windows(2).all(|r| r[1] < r[0] + 0.1));
+        true
+    })).success();
+}
+
+#[test]
+fn stroke_outline() {
+    let svg = |attrs: &str, d: &str| format!(
+        r#"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg">
+<path stroke="black" fill="none" stroke-width="10" {} d="{}"/></svg>"#, attrs, d);
+    let cases = [
+        (svg("", "M 10 50 L 90 50"),
+         vec![(10.0, 45.0), (90.0, 45.0), (90.0, 55.0), (10.0, 55.0), (10.0, 45.0)]),
+        (svg(r#"stroke-linecap="square""#, "M 10 50 L 90 50"),
+         vec![(10.0, 45.0), (90.0, 45.0), (95.0, 45.0), (95.0, 55.0),
+              (90.0, 55.0), (10.0, 55.0), (5.0, 55.0), (5.0, 45.0), (10.0, 45.0)]),
+        (svg("", "M 20 20 h 60 v 60 h -60 Z"),
+         vec![(25.0, 75.0), (75.0, 75.0), (75.0, 25.0), (25.0, 25.0), (25.0, 75.0),
+              (15.0, 15.0), (85.0, 15.0), (85.0, 85.0), (15.0, 85.0), (15.0, 15.0)]),
+    ];
+    for (input, expected) in cases {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        let assert = cmd.args(["--stroke-outline"]).write_stdin(input).assert();
+        assert.stdout(predicate::function(|out: &str| {
+            let p = extract_pts(out).unwrap();
+            let expected: Vec<Pt> = expected.iter().map(|&pt| pt.into()).collect();
+            assert_eq!(p, expected);
+            true
+        })).success();
+    }
+
+    // Round caps stay within half the stroke width of the center line
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    let assert = cmd.args(["--stroke-outline"])
+        .write_stdin(svg(r#"stroke-linecap="round""#, "M 10 50 L 90 50")).assert();
+    assert.stdout(predicate::function(|out: &str| {
+        let p = extract_pts(out).unwrap();
+        assert_lt!(10, p.len());
+        for pt in p {
+            let nearest = Pt::new(pt.x.clamp(10.0, 90.0), 50.0);
+            assert_lt!(((pt - nearest).length() - 5.0).abs(), 1e-9);
+        }
+        true
+    })).success();
+}
+
+#[test]
+fn blank_lines_between_subpaths() {
+    let svg = r#"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg">
+<path stroke="black" d="M 0 0 L 10 0 M 0 10 L 10 10"/><path stroke="black" d="M 0 20 L 10 20"/></svg>"#;
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    cmd.args(["-b"]).write_stdin(svg).assert().success()
+        .stdout("0.0 100.0\n10.0 100.0\n\n0.0 90.0\n10.0 90.0\n\n0.0 80.0\n10.0 80.0\n");
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    cmd.write_stdin(svg).assert().success()
+        .stdout("0.0 100.0\n10.0 100.0\n0.0 90.0\n10.0 90.0\n0.0 80.0\n10.0 80.0\n");
+}
+
+#[test]
+fn dashed_strokes() {
+    let svg = |dash: &str| format!(
+        r#"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg">
+<path stroke="black" {} d="M 0 50 L 100 50"/></svg>"#, dash);
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    let assert = cmd.args(["--dashes", "-b"])
+        .write_stdin(svg(r#"stroke-dasharray="10 5""#)).assert();
+    assert.stdout(predicate::function(|out: &str| {
+        let dashes: Vec<Vec<Pt>> = out.split("\n\n").map(|dash| extract_pts(dash).unwrap()).collect();
+        assert_eq!(dashes.len(), 7);
+        for (i, dash) in dashes.iter().enumerate() {
+            assert_eq!(dash.len(), 2);
+            assert_lt!((dash[0].x - 15.0 * i as f64).abs(), 1e-9);
+            assert_lt!((dash[1].x - (15.0 * i as f64 + 10.0)).abs(), 1e-9);
+        }
+        true
+    })).success();
+
+    // An odd dasharray is repeated, the offset shifts the pattern
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    let assert = cmd.args(["--dashes", "-b"])
+        .write_stdin(svg(r#"stroke-dasharray="20" stroke-dashoffset="30""#)).assert();
+    assert.stdout(predicate::function(|out: &str| {
+        let dashes: Vec<Vec<Pt>> = out.split("\n\n").map(|dash| extract_pts(dash).unwrap()).collect();
+        let xs: Vec<(f64, f64)> = dashes.iter().map(|d| (d[0].x.round(), d[1].x.round())).collect();
+        assert_eq!(xs, [(10.0, 30.0), (50.0, 70.0), (90.0, 100.0)]);
+        true
+    })).success();
+}
+
+#[test]
+fn precision_and_quantize() {
+    let svg = r#"<svg viewBox="0 0 10 10" xmlns="http://www.w3.org/2000/svg">
+<path stroke="black" d="M 0.123 1.987 L 1.2 1.6 L 1.3 1.7 L 3.3333 9"/>
+</svg>"#;
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        let out = cmd.args(args).args(["--y-down"]).write_stdin(svg.to_string()).assert().success()
+            .get_output().stdout.clone();
+        String::from_utf8(out).unwrap()
+    };
+
+    assert_eq!(run(&["--precision", "2"]), "0.12 1.99\n1.20 1.60\n1.30 1.70\n3.33 9.00\n");
+    assert_eq!(run(&["--precision", "0"]), "0 2\n1 2\n1 2\n3 9\n");
+    assert_eq!(run(&["--quantize", "0.5"]), "0.0 2.0\n1.0 1.5\n1.5 1.5\n3.5 9.0\n");
+    assert_eq!(run(&["--quantize", "1"]), "0 2\n1 2\n1 2\n3 9\n");
+    assert_eq!(run(&["--quantize", "1", "--skip-repeats"]), "0 2\n1 2\n3 9\n");
+    assert_eq!(run(&["--quantize", "1", "--precision", "1"]), "0.0 2.0\n1.0 2.0\n1.0 2.0\n3.0 9.0\n");
+    assert_eq!(run(&["--precision", "0", "--skip-repeats"]), "0 2\n1 2\n3 9\n");
+
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    cmd.args(["--quantize", "0"]).write_stdin(svg).assert().failure();
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    cmd.args(["--precision", "13"]).write_stdin(svg).assert().failure();
+}
+
+#[test]
+fn travel_ordering() {
+    let svg = r#"<svg viewBox="0 0 100 20" xmlns="http://www.w3.org/2000/svg">
+<path stroke="black" d="M 50 0 L 60 0"/>
+<path stroke="black" d="M 10 0 C 10 5 0 5 0 0"/>
+<path stroke="black" d="M 30 10 h 10 v -10 h -10 Z"/>
+</svg>"#;
+    let run = |args: &[&str], svg: &str| {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        let out = cmd.args(args).write_stdin(svg.to_string()).assert().success().get_output().clone();
+        let travel = String::from_utf8(out.stderr).unwrap();
+        let travel: Vec<f64> = travel.split(' ')
+            .filter_map(|word| word.trim_end_matches(',').parse().ok())
+            .collect();
+        (extract_pts(&String::from_utf8(out.stdout).unwrap()).unwrap(), travel)
+    };
+
+    // The curve is drawn backwards from the pen at 0 0, and the square
+    // starts at its corner closest to the end of the curve.
+    let (pts, travel) = run(&["--y-down", "--order", "greedy"], svg);
+    assert_eq!(pts[0], Pt::new(0.0, 0.0));
+    assert_eq!(pts[14], Pt::new(10.0, 0.0));
+    assert_eq!(&pts[15..], &[(30.0, 0.0), (30.0, 10.0), (40.0, 10.0), (40.0, 0.0), (30.0, 0.0),
+                             (50.0, 0.0), (60.0, 0.0)].map(Pt::from));
+    assert!((travel[0] - (100.0 + 1000f64.sqrt())).abs() < 1e-9);
+    assert_eq!(travel[1], 40.0);
+
+    // Short lines scattered around, the same lines are drawn in any order.
+    let mut seed = 1u64;
+    let mut random = || {
+        seed = (seed * 1103515245 + 12345) % (1 << 31);
+        seed % 100
+    };
+    let mut scattered = String::from(r#"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg">"#);
+    for _ in 0..40 {
+        let (x0, y0, x1, y1) = (random(), random(), random(), random());
+        scattered += &format!(r#"<path stroke="black" d="M {x0} {y0} L {x1} {y1}"/>"#);
+    }
+    scattered += "</svg>";
+    let lines = |pts: &[Pt]| {
+        let mut lines: Vec<_> = pts.chunks(2)
+            .map(|line| if (line[0].x, line[0].y) < (line[1].x, line[1].y) {
+                (line[0].x, line[0].y, line[1].x, line[1].y)
+            } else {
+                (line[1].x, line[1].y, line[0].x, line[0].y)
+            })
+            .collect();
+        lines.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        lines
+    };
+    let (document, _) = run(&[], &scattered);
+    let (greedy, greedy_travel) = run(&["--order", "greedy"], &scattered);
+    let (two_opt, two_opt_travel) = run(&["--order", "two-opt"], &scattered);
+    assert_eq!(lines(&document), lines(&greedy));
+    assert_eq!(lines(&document), lines(&two_opt));
+    assert!(greedy_travel[1] < greedy_travel[0] / 2.0);
+    assert!(two_opt_travel[1] <= greedy_travel[1]);
+
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    cmd.args(["--order", "fastest"]).write_stdin(svg).assert().failure();
+}
+
+#[test]
+fn join_paths() {
+    let svg = r#"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg">
+<path stroke="black" d="M 0 0 L 10 0"/>
+<path stroke="black" d="M 10 10 L 10.05 0"/>
+<path stroke="black" d="M 50 50 L 60 50"/>
+<path stroke="black" d="M 0 10 L 10 10"/>
+<path stroke="black" d="M 0 0 L 0 10"/>
+</svg>"#;
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        let out = cmd.args(["--y-down", "-b"]).args(args).write_stdin(svg.to_string()).assert().success()
+            .get_output().stdout.clone();
+        String::from_utf8(out).unwrap()
+    };
+
+    // The gap is bridged, the pieces drawn backwards where needed, and the
+    // loop closed.
+    assert_eq!(run(&["--join", "0.1"]),
+               "0.0 0.0\n10.0 0.0\n10.05 0.0\n10.0 10.0\n0.0 10.0\n0.0 0.0\n\n50.0 50.0\n60.0 50.0\n");
+    // The spacing carries on across the joins.
+    let pts = extract_pts(&run(&["--join", "0.1", "-d", "7"]).replace("\n\n", "\n")).unwrap();
+    for w in pts[..5].windows(2) {
+        assert!(((w[1] - w[0]).length() - 7.0).abs() < 0.5);
+    }
+    // The gap is too wide to close the loop, the first path keeps its
+    // direction.
+    assert_eq!(run(&["--join", "0.01"]),
+               "10.05 0.0\n10.0 10.0\n0.0 10.0\n0.0 0.0\n10.0 0.0\n\n50.0 50.0\n60.0 50.0\n");
+}
+
+#[test]
+fn remove_duplicates() {
+    // A filled square and its stroked copy drawn the other way round, a
+    // curve drawn twice, and lines that partly run over earlier ones.
+    let svg = r#"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg">
+<path fill="red" d="M 0 0 h 10 v 10 h -10 Z"/>
+<path stroke="black" d="M 0 10 h 10 v -10 h -10 Z"/>
+<path stroke="black" d="M 20 0 C 30 10 40 10 50 0"/>
+<path stroke="black" d="M 50.01 0 C 40 10 30 10 20 0"/>
+<path stroke="black" d="M 5 0 L 15 0 L 15 5"/>
+<path stroke="black" d="M 0 20 L 30 20"/>
+<path stroke="black" d="M 40 20 L 20 20 L 10 20"/>
+</svg>"#;
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        let out = cmd.args(["--y-down", "-b"]).args(args).write_stdin(svg.to_string()).assert().success()
+            .get_output().clone();
+        (String::from_utf8(out.stdout).unwrap(), String::from_utf8(out.stderr).unwrap())
+    };
+
+    let (out, report) = run(&["--dedup", "0.05"]);
+    assert_eq!(report, "removed 2 duplicate paths, trimmed 3 overlapping segments\n");
+    let paths: Vec<&str> = out.split("\n\n").collect();
+    assert_eq!(paths.len(), 5);
+    assert_eq!(paths[0], "0.0 0.0\n10.0 0.0\n10.0 10.0\n0.0 10.0\n0.0 0.0");
+    assert!(paths[1].starts_with("20.0 0.0\n") && paths[1].ends_with("\n50.0 0.0"));
+    assert_eq!(paths[2], "10.0 0.0\n15.0 0.0\n15.0 5.0");
+    assert_eq!(paths[3], "0.0 20.0\n30.0 20.0");
+    assert_eq!(paths[4], "40.0 20.0\n30.0 20.0\n");
+
+    // The copy of the curve is too far off to be a duplicate.
+    let (_, report) = run(&["--dedup", "0.001"]);
+    assert_eq!(report, "removed 1 duplicate paths, trimmed 3 overlapping segments\n");
+    // Lines shorter than the tolerance are kept unless covered.
+    let short = r#"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg">
+<path stroke="black" d="M 0 0 L 1 0"/><path stroke="black" d="M 0 5 L 1 5 L 1 6"/></svg>"#;
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    cmd.args(["--y-down", "--dedup", "2"]).write_stdin(short).assert().success()
+        .stdout("0.0 0.0\n1.0 0.0\n0.0 5.0\n1.0 5.0\n1.0 6.0\n");
+
+    // Segments at a slight angle are covered where they run within the
+    // tolerance, whichever is drawn first.
+    let slanted = |first: &str, second: &str| {
+        let svg = format!(r#"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg">
+<path stroke="black" d="{}"/><path stroke="black" d="{}"/></svg>"#, first, second);
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        let out = cmd.args(["--y-down", "-b", "--dedup", "0.05"]).write_stdin(svg).assert().success()
+            .stderr("removed 0 duplicate paths, trimmed 1 overlapping segments\n").get_output().stdout.clone();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!(slanted("M 0 0 L 10 0", "M 2 0.02 L 3 -0.02"), "0.0 0.0\n10.0 0.0\n");
+    let paths = slanted("M 2 0.02 L 3 -0.02", "M 0 0 L 10 0");
+    let paths: Vec<Vec<Pt>> = paths.split("\n\n").map(|path| extract_pts(path).unwrap()).collect();
+    assert_eq!(paths.len(), 3);
+    assert_lt!((paths[1][1].x - 2.0).abs(), 0.01);
+    assert_lt!((paths[2][0].x - 3.0).abs(), 0.01);
+
+    // Without --dedup, or with nothing to remove, nothing is reported.
+    assert_eq!(run(&[]).1, "");
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    cmd.args(["--dedup", "0.05"]).write_stdin(DATA_LINE_AND_SQUARE).assert().success().stderr("");
+}
+
+#[test]
+fn loop_orientation_and_start() {
+    let svg = r#"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg">
+<path fill="black" d="M 10 10 h 80 v 80 h -80 Z M 30 30 h 40 v 40 h -40 Z"/>
+<path stroke="black" d="M 60 5 L 50 0 L 40 5 Z"/>
+</svg>"#;
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        let out = cmd.args(["--y-down", "-b"]).args(args).write_stdin(svg.to_string()).assert().success()
+            .get_output().stdout.clone();
+        let out = String::from_utf8(out).unwrap();
+        out.split("\n\n").map(|subpath| extract_pts(subpath).unwrap()).collect::<Vec<_>>()
+    };
+    // Twice the area, positive when clockwise as seen with y pointing down.
+    let area = |ring: &[Pt]| ring.windows(2).map(|w| w[0].cross(w[1])).sum::<f64>();
+
+    for (orientation, outer, hole, triangle) in [
+        ("cw", 1.0, 1.0, 1.0),
+        ("ccw", -1.0, -1.0, -1.0),
+        ("cw-outer", 1.0, -1.0, 1.0),
+        ("ccw-outer", -1.0, 1.0, -1.0),
+    ] {
+        let loops = run(&["--orientation", orientation]);
+        assert_eq!(area(&loops[0]).signum(), outer, "{orientation}");
+        assert_eq!(area(&loops[1]).signum(), hole, "{orientation}");
+        assert_eq!(area(&loops[2]).signum(), triangle, "{orientation}");
+    }
+
+    let starts = |args: &[&str]| run(args).iter().map(|ring| (ring[0].x, ring[0].y)).collect::<Vec<_>>();
+    assert_eq!(starts(&["--start", "topmost"]), [(10.0, 10.0), (30.0, 30.0), (50.0, 0.0)]);
+    assert_eq!(starts(&["--start", "100,0"]), [(90.0, 10.0), (70.0, 30.0), (60.0, 5.0)]);
+    // The pen starts at the origin, each loop starts closest to where
+    // the one before ended.
+    assert_eq!(starts(&["--start", "nearest"]), [(10.0, 10.0), (30.0, 30.0), (40.0, 5.0)]);
+    // Loops still end where they start.
+    for ring in run(&["--start", "nearest", "--orientation", "cw"]) {
+        assert_eq!(ring[0], ring[ring.len() - 1]);
+    }
+
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    cmd.args(["--start", "middle"]).write_stdin(svg).assert().failure();
+}
+
+#[test]
+fn select_by_id_and_layer() {
+    let svg = r#"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape">
+<g id="layer1" inkscape:groupmode="layer" inkscape:label="Front" transform="translate(10 0)">
+  <path id="a" stroke="black" d="M 0 0 L 1 0"/>
+  <g id="inner"><path id="b" stroke="black" d="M 0 1 L 1 1"/></g>
+</g>
+<g id="layer2" inkscape:groupmode="layer" inkscape:label="Back">
+  <path id="c" stroke="black" d="M 0 2 L 1 2"/>
+</g>
+</svg>"#;
+    // The first point of every path, the layer transform still applies
+    // with the groups kept.
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        let out = cmd.arg("--y-down").args(args).write_stdin(svg.to_string()).assert().success()
+            .get_output().stdout.clone();
+        let pts = extract_pts(&String::from_utf8(out).unwrap()).unwrap();
+        pts.iter().step_by(2).map(|pt| (pt.x, pt.y)).collect::<Vec<_>>()
+    };
+
+    assert_eq!(run(&[]), [(10.0, 0.0), (10.0, 1.0), (0.0, 2.0)]);
+    assert_eq!(run(&["--select", "a"]), [(10.0, 0.0)]);
+    assert_eq!(run(&["--select", "inner,c"]), [(10.0, 1.0), (0.0, 2.0)]);
+    assert_eq!(run(&["--layer", "Front"]), [(10.0, 0.0), (10.0, 1.0)]);
+    assert_eq!(run(&["--layer", "Front", "--exclude", "b"]), [(10.0, 0.0)]);
+    assert_eq!(run(&["--exclude", "layer1"]), [(0.0, 2.0)]);
+    assert_eq!(run(&["--layer", "Back", "--select", "a"]), [(10.0, 0.0), (0.0, 2.0)]);
+
+    for args in [["--select", "nope"], ["--layer", "Side"]] {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        cmd.args(args).write_stdin(svg).assert().failure();
+    }
+}
+
+#[test]
+fn split_by_color() {
+    let svg = r##"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg">
+<path stroke="red" d="M 0 0 L 1 0"/>
+<path stroke="blue" d="M 0 1 L 1 1"/>
+<path stroke="#ff0000" fill="blue" d="M 0 2 L 1 2"/>
+<path fill="blue" d="M 0 3 L 1 3"/>
+</svg>"##;
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    cmd.args(["--y-down", "--by-color", "stroke"]).write_stdin(svg).assert().success().stdout(
+        "# pen 1 #ff0000\n0.0 0.0\n1.0 0.0\n0.0 2.0\n1.0 2.0\n\
+         # pen 2 #0000ff\n0.0 1.0\n1.0 1.0\n\
+         # pen 3 none\n0.0 3.0\n1.0 3.0\n");
+
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("out.pts");
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    let input = dir.path().join("in.svg");
+    std::fs::write(&input, svg).unwrap();
+    cmd.args(["--y-down", "-b", "--by-color", "fill"]).arg(&input).arg(&output).assert().success();
+
+    // Unfilled paths are filled black by default.
+    let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+    assert_eq!(read("out_000000.pts"), "0.0 0.0\n1.0 0.0\n\n0.0 1.0\n1.0 1.0\n");
+    assert_eq!(read("out_0000ff.pts"), "0.0 2.0\n1.0 2.0\n\n0.0 3.0\n1.0 3.0\n");
+    assert!(!output.exists());
+
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    cmd.args(["--y-down", "--by-color", "fill", "--pen-markers"]).arg(&input).arg(&output).assert().success();
+    assert_eq!(read("out.pts"), "# pen 1 #000000\n0.0 0.0\n1.0 0.0\n0.0 1.0\n1.0 1.0\n\
+                                 # pen 2 #0000ff\n0.0 2.0\n1.0 2.0\n0.0 3.0\n1.0 3.0\n");
+
+    // Paths of different colors are neither joined nor duplicates.
+    let touching = r##"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg">
+<path stroke="red" d="M 0 0 L 1 0"/>
+<path stroke="blue" d="M 1 0 L 2 0"/>
+<path stroke="blue" d="M 0 0 L 1 0"/>
+<path stroke="red" d="M 2 0 L 3 0"/>
+</svg>"##;
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    cmd.args(["--y-down", "-b", "--join", "0.1", "--by-color", "stroke"]).write_stdin(touching).assert().success()
+        .stdout("# pen 1 #ff0000\n0.0 0.0\n1.0 0.0\n\n2.0 0.0\n3.0 0.0\n\
+                 # pen 2 #0000ff\n0.0 0.0\n1.0 0.0\n2.0 0.0\n");
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    cmd.args(["--y-down", "-b", "--dedup", "0.1", "--by-color", "stroke"]).write_stdin(touching).assert().success()
+        .stderr("").stdout("# pen 1 #ff0000\n0.0 0.0\n1.0 0.0\n\n2.0 0.0\n3.0 0.0\n\
+                            # pen 2 #0000ff\n1.0 0.0\n2.0 0.0\n\n0.0 0.0\n1.0 0.0\n");
+}
+
+#[test]
+fn split_output() {
+    let svg = r#"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape">
+<g id="layer1" inkscape:groupmode="layer" inkscape:label="Front">
+  <path id="a" stroke="black" d="M 0 0 L 1 0"/>
+  <g id="inner"><path id="b" stroke="black" d="M 0 1 L 1 1"/></g>
+</g>
+<path stroke="black" d="M 0 2 L 1 2"/>
+</svg>"#;
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("drawing.svg");
+    std::fs::write(&input, svg).unwrap();
+    let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+    let files = || {
+        let mut files: Vec<String> = std::fs::read_dir(dir.path()).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name != "drawing.svg")
+            .collect();
+        files.sort();
+        files
+    };
+
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    cmd.args(["--y-down", "--split", "path"]).arg(&input).assert().success().stdout("");
+    assert_eq!(files(), ["drawing_a_0.pts", "drawing_b_1.pts", "drawing_path_2.pts"]);
+    assert_eq!(read("drawing_b_1.pts"), "0.0 1.0\n1.0 1.0\n");
+    for file in files() {
+        std::fs::remove_file(dir.path().join(file)).unwrap();
+    }
+
+    // Every file keeps a single path, though their ends meet.
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    cmd.args(["--y-down", "--split", "path", "--join", "1.5", "--dedup", "1.5"]).arg(&input).assert().success();
+    assert_eq!(files(), ["drawing_a_0.pts", "drawing_b_1.pts", "drawing_path_2.pts"]);
+    assert_eq!(read("drawing_a_0.pts"), "0.0 0.0\n1.0 0.0\n");
+    assert_eq!(read("drawing_path_2.pts"), "0.0 2.0\n1.0 2.0\n");
+    for file in files() {
+        std::fs::remove_file(dir.path().join(file)).unwrap();
+    }
+
+    // Named after the output, which is not written.
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    cmd.args(["--y-down", "--split", "group", "--split-template", "{index}-{stem}-{id}.txt"])
+        .arg(&input).arg(dir.path().join("plot.pts")).assert().success();
+    assert_eq!(files(), ["0-plot-layer1.txt", "1-plot-group.txt"]);
+    assert_eq!(read("0-plot-layer1.txt"), "0.0 0.0\n1.0 0.0\n0.0 1.0\n1.0 1.0\n");
+    assert_eq!(read("1-plot-group.txt"), "0.0 2.0\n1.0 2.0\n");
+
+    let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+    cmd.args(["--split", "path"]).write_stdin(svg).assert().failure();
+}
+
+#[test]
+fn visibility_rules() {
+    let svg = r#"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg">
+<path id="guide" fill="none" stroke="none" d="M 0 0 L 1 0"/>
+<path id="seen" stroke="black" d="M 0 1 L 1 1"/>
+<path id="faded" stroke="black" opacity="0" d="M 0 2 L 1 2"/>
+<g opacity="0"><path stroke="black" d="M 0 3 L 1 3"/></g>
+<path id="clear" fill="transparent" stroke="black" stroke-opacity="0" d="M 0 4 L 1 4"/>
+<path id="hidden" stroke="black" visibility="hidden" d="M 0 5 L 1 5"/>
+<path fill="none" d="M 0 6 L 1 6"/>
+</svg>"#;
+    // The y of every path, each is a line along x.
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("svg2pts").unwrap();
+        let out = cmd.arg("--y-down").args(args).write_stdin(svg.to_string()).assert().success()
+            .get_output().clone();
+        let pts = extract_pts(&String::from_utf8(out.stdout).unwrap()).unwrap();
+        let ys: Vec<f64> = pts.iter().step_by(2).map(|pt| pt.y).collect();
+        (ys, String::from_utf8(out.stderr).unwrap())
+    };
+
+    assert_eq!(run(&[]), (vec![1.0, 2.0, 3.0, 4.0, 5.0], String::new()));
+    assert_eq!(run(&["--unpainted", "include"]).0, [0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+    assert_eq!(run(&["--unpainted", "only"]).0, [0.0, 6.0]);
+    assert_eq!(run(&["--skip-invisible", "--report-skipped"]), (vec![1.0], "\
+skipped path 1 'guide': no fill or stroke
+skipped path 3 'faded': opacity 0
+skipped path 4: opacity 0
+skipped path 5 'clear': transparent paint
+skipped path 6 'hidden': visibility hidden
+skipped path 7: no fill or stroke
+".to_string()));
+    let (ys, report) = run(&["--unpainted", "only", "--select", "guide", "--report-skipped"]);
+    assert_eq!(ys, [0.0]);
+    assert!(report.contains("skipped path 2 'seen': not selected\n"));
+    assert_eq!(report.lines().count(), 6);
+}